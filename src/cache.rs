@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::Duration,
+};
+
+/// Map whose entries expire `ttl` after insertion. Expired entries are dropped lazily on access,
+/// from the least recently used end, and inserting past `capacity` evicts the least recently used entry. Callers pass the time in,
+/// so the store's clock decides when entries expire.
+#[derive(Debug)]
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by last use, oldest first
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted: DateTime<Utc>,
+    used: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> TtlCache<K, V> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Live value for the key, marking it as recently used
+    pub fn get(&mut self, key: &K, now: DateTime<Utc>) -> Option<&V> {
+        self.evict_expired(now);
+        // Eviction stops at the first live entry, so a recently used one may have expired behind it
+        if self.entries.get(key).is_some_and(|entry| expired(entry, self.ttl, now)) {
+            let entry = self.entries.remove(key)?;
+            self.recency.remove(&entry.used);
+            return None;
+        }
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used);
+        entry.used = self.tick;
+        self.recency.insert(self.tick, key.clone());
+        Some(&entry.value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V, now: DateTime<Utc>) {
+        self.evict_expired(now);
+        if let Some(old) = self.entries.remove(&key) {
+            self.recency.remove(&old.used);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else { break };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, Entry { value, inserted: now, used: self.tick });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops expired entries from the least recently used end, stopping at the first live one,
+    /// so the work done under the store lock is bounded by what actually expired
+    fn evict_expired(&mut self, now: DateTime<Utc>) {
+        while let Some(oldest) = self.recency.first_entry() {
+            let stale = self.entries.get(oldest.get()).is_some_and(|entry| expired(entry, self.ttl, now));
+            if !stale {
+                break;
+            }
+            self.entries.remove(&oldest.remove());
+        }
+    }
+}
+
+fn expired<V>(entry: &Entry<V>, ttl: Duration, now: DateTime<Utc>) -> bool {
+    // A clock that went back leaves the entry live rather than expiring it early
+    (now - entry.inserted).to_std().is_ok_and(|age| age >= ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let start = start();
+        let mut cache = TtlCache::new(TTL, 10);
+        cache.insert("a", 1, start);
+
+        assert_eq!(cache.get(&"a", start + chrono::Duration::seconds(9)), Some(&1));
+        assert_eq!(cache.get(&"a", start + chrono::Duration::seconds(10)), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_expired_entry_behind_a_live_one_is_not_served() {
+        let start = start();
+        let mut cache = TtlCache::new(TTL, 10);
+        cache.insert("a", 1, start);
+        cache.insert("b", 2, start + chrono::Duration::seconds(5));
+        // "a" is now the most recently used, behind a live "b"
+        cache.get(&"a", start + chrono::Duration::seconds(6));
+
+        let later = start + chrono::Duration::seconds(11);
+        assert_eq!(cache.get(&"a", later), None);
+        assert_eq!(cache.get(&"b", later), Some(&2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_overflow_evicts_least_recently_used() {
+        let now = start();
+        let mut cache = TtlCache::new(TTL, 2);
+        cache.insert("a", 1, now);
+        cache.insert("b", 2, now);
+        // Touching "a" leaves "b" as the oldest
+        cache.get(&"a", now);
+        cache.insert("c", 3, now);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b", now), None);
+        assert_eq!(cache.get(&"a", now), Some(&1));
+        assert_eq!(cache.get(&"c", now), Some(&3));
+    }
+
+    #[test]
+    fn test_reinsert_replaces_without_growing() {
+        let now = start();
+        let mut cache = TtlCache::new(TTL, 2);
+        cache.insert("a", 1, now);
+        cache.insert("a", 2, now);
+        cache.insert("b", 3, now);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a", now), Some(&2));
+    }
+}
@@ -0,0 +1,168 @@
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use uuid::Uuid;
+
+use crate::order_dtos::{CreateOrderDto, ListQuery, OrderResponseDto, UpdateStatusDto};
+
+/// Error body the API sends with every non-2xx response
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+/// Client side mirror of the server's `ApiError`
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The server answered with a non-2xx status; `body.code` tells the cases apart
+    #[error("{status}: {}", .body.message)]
+    Status { status: StatusCode, body: ErrorBody },
+    /// No well-formed answer: connection failure, timeout or an unreadable body
+    #[error("Request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+}
+
+impl ApiError {
+    /// Machine readable code, such as `order_not_found` or `validation_failed`
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ApiError::Status { body, .. } => Some(&body.code),
+            ApiError::Transport(_) => None,
+        }
+    }
+}
+
+/// Typed calls to the orders API
+#[derive(Debug, Clone)]
+pub struct OrdersClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl OrdersClient {
+    /// `base_url` is where the API is served, e.g. `http://127.0.0.1:3000`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_client(base_url, reqwest::Client::new())
+    }
+
+    /// Like `new`, reusing a configured `reqwest::Client` (timeouts, proxies, default headers)
+    pub fn with_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { base_url, http }
+    }
+
+    pub async fn create(&self, order: &CreateOrderDto) -> Result<OrderResponseDto, ApiError> {
+        let request = self.http.post(self.url("/orders")).json(order);
+        json(send(request).await?).await
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<OrderResponseDto, ApiError> {
+        let request = self.http.get(self.url(&format!("/orders/{id}")));
+        json(send(request).await?).await
+    }
+
+    /// One page of orders; results past the server's `MAX_RESULTS` cap are dropped
+    pub async fn list(&self, query: &ListQuery) -> Result<Vec<OrderResponseDto>, ApiError> {
+        let request = self.http.get(self.url("/orders")).query(query);
+        json(send(request).await?).await
+    }
+
+    pub async fn update_status(&self, id: Uuid, update: &UpdateStatusDto) -> Result<OrderResponseDto, ApiError> {
+        let request = self.http.put(self.url(&format!("/orders/{id}/status"))).json(update);
+        json(send(request).await?).await
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<(), ApiError> {
+        send(self.http.delete(self.url(&format!("/orders/{id}")))).await?;
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+/// Sends the request, turning non-2xx answers into `ApiError::Status`
+async fn send(request: RequestBuilder) -> Result<Response, ApiError> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await?;
+    // Rejections axum produces itself (e.g. a wrong content type) come back as plain text
+    let body = serde_json::from_str(&text).unwrap_or(ErrorBody {
+        code: "unknown".into(),
+        message: text,
+        details: None,
+    });
+    Err(ApiError::Status { status, body })
+}
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T, ApiError> {
+    Ok(response.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, db::Store, routes};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    /// Serves a fresh app on an ephemeral port and returns a client pointed at it
+    async fn spawn_server() -> OrdersClient {
+        let db = Arc::new(RwLock::new(Store::default()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, routes::app(db, &Config::default())).await.unwrap() });
+        OrdersClient::new(format!("http://{addr}/"))
+    }
+
+    fn order(customer: &str) -> CreateOrderDto {
+        CreateOrderDto {
+            customer: customer.to_string(),
+            items: vec!["Book".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let client = spawn_server().await;
+
+        let created = client.create(&order("Alice")).await.unwrap();
+        assert_eq!(created.status, "pending");
+        assert_eq!(client.get(created.id).await.unwrap().customer, "Alice");
+
+        let update = UpdateStatusDto { status: "shipped".to_string(), expected: None };
+        assert_eq!(client.update_status(created.id, &update).await.unwrap().status, "shipped");
+
+        client.create(&order("Bob")).await.unwrap();
+        let query = ListQuery { status: Some("shipped".to_string()), ..Default::default() };
+        let shipped = client.list(&query).await.unwrap();
+        assert_eq!(shipped.len(), 1);
+        assert_eq!(shipped[0].id, created.id);
+
+        client.delete(created.id).await.unwrap();
+        let err = client.get(created.id).await.unwrap_err();
+        assert!(matches!(err, ApiError::Status { status: StatusCode::NOT_FOUND, .. }));
+        assert_eq!(err.code(), Some("order_not_found"));
+    }
+
+    #[tokio::test]
+    async fn test_client_maps_error_bodies() {
+        let client = spawn_server().await;
+
+        let err = client.create(&order("")).await.unwrap_err();
+        let ApiError::Status { status, body } = err else { panic!("expected a status error") };
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body.code, "validation_failed");
+        assert!(body.details.unwrap()["customer"].is_array());
+
+        let created = client.create(&order("Alice")).await.unwrap();
+        let update = UpdateStatusDto { status: "delivered".to_string(), expected: Some("shipped".to_string()) };
+        let err = client.update_status(created.id, &update).await.unwrap_err();
+        assert_eq!(err.code(), Some("status_mismatch"));
+    }
+}
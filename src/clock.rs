@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// Source of wall-clock time for everything the store timestamps
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl Default for Box<dyn Clock> {
+    fn default() -> Self {
+        Box::new(SystemClock)
+    }
+}
+
+/// Clock that only moves when told to; clones share the same instant
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct MockClock(std::sync::Arc<std::sync::Mutex<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(at: DateTime<Utc>) -> Self {
+        Self(std::sync::Arc::new(std::sync::Mutex::new(at)))
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_only_moves_when_advanced() {
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().to_utc();
+        let clock = MockClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(clock.now(), start);
+
+        shared.advance(chrono::Duration::seconds(90));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(90));
+    }
+}
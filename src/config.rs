@@ -0,0 +1,247 @@
+use std::{env, time::Duration};
+
+use crate::{errors::ErrorFormat, logging::LatencyBudgets, order_dtos::{ItemNormalization, LengthLimits, ListOrder, StatusCase}, timeouts::RouteTimeouts};
+
+/// Settings read from the environment (and `.env`)
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: String,
+    /// Also enables panic messages in 500 bodies, which production must not expose
+    pub dev_logging: bool,
+    /// Identical creates within this window are rejected as duplicates (off when unset)
+    pub dedup_window: Option<Duration>,
+    /// Most recent creates remembered for duplicate detection; the least recently seen go first
+    pub dedup_max_entries: usize,
+    /// How long a deleted order can be brought back with `POST /orders/{id}/restore` (off when unset)
+    pub undo_window: Option<Duration>,
+    /// Cap on simultaneously open connections (unlimited when unset)
+    pub max_connections: Option<usize>,
+    /// `max-age` sent with successful GET responses (no `Cache-Control` when unset)
+    pub cache_max_age: Option<Duration>,
+    /// Log one in this many requests; 0 logs only errors and slow requests
+    pub log_sample_rate: u64,
+    /// Requests slower than this are always logged
+    pub log_slow_threshold: Duration,
+    /// Waiting longer than this for the store lock logs a warning naming the waiting operation
+    pub slow_lock_threshold: Duration,
+    /// When false, every route that changes orders answers 403
+    pub enable_writes: bool,
+    /// Reject requests whose `X-Forwarded-Proto` is not https, for deployments behind a TLS-terminating proxy
+    pub require_https: bool,
+    /// Reject JSON bodies carrying fields the endpoint does not know instead of ignoring them
+    pub strict_json: bool,
+    /// Whether status changes may name the status in any case
+    pub status_case: StatusCase,
+    /// Layout of validation error details
+    pub error_format: ErrorFormat,
+    /// Most items an unpaginated endpoint returns before truncating (no cap when None)
+    pub max_results: Option<usize>,
+    /// Order of list results when no `sort` is given
+    pub list_order: ListOrder,
+    /// Rewriting applied to item names on create and patch
+    pub item_normalization: ItemNormalization,
+    /// Longest accepted names on create, patch, import and restore
+    pub length_limits: LengthLimits,
+    /// Most edits (Levenshtein distance) between a customer name and the query in fuzzy search
+    pub fuzzy_max_distance: usize,
+    /// Per-route latency above which a warning is logged
+    pub latency_budgets: LatencyBudgets,
+    /// How long a request may take before it is answered with 503, globally and per route
+    pub request_timeouts: RouteTimeouts,
+    /// Origins allowed to call the API from a browser; `*` for any, empty disables CORS
+    pub cors_origins: Vec<String>,
+    /// Lets browsers send cookies and auth headers cross-origin
+    pub cors_allow_credentials: bool,
+    /// How long browsers may cache a preflight answer
+    pub cors_max_age: Option<Duration>,
+    /// How often the event log is snapshotted and trimmed (never when unset)
+    pub event_compaction_interval: Option<Duration>,
+    /// Events kept behind each snapshot, so slightly stale readers can still catch up
+    pub event_retention: usize,
+    /// Threads CPU-heavy jobs (such as CSV export) may use at once
+    pub blocking_workers: usize,
+    /// Jobs allowed to wait for a worker before further ones get 503
+    pub blocking_queue: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: "3000".to_string(),
+            dev_logging: false,
+            dedup_window: None,
+            dedup_max_entries: 10_000,
+            undo_window: None,
+            max_connections: None,
+            cache_max_age: None,
+            log_sample_rate: 1,
+            log_slow_threshold: Duration::from_millis(500),
+            slow_lock_threshold: Duration::from_millis(100),
+            enable_writes: true,
+            require_https: false,
+            strict_json: false,
+            status_case: StatusCase::default(),
+            error_format: ErrorFormat::default(),
+            max_results: Some(1000),
+            list_order: ListOrder::default(),
+            item_normalization: ItemNormalization::default(),
+            length_limits: LengthLimits::default(),
+            fuzzy_max_distance: 2,
+            latency_budgets: LatencyBudgets::default(),
+            request_timeouts: RouteTimeouts::default(),
+            cors_origins: Vec::new(),
+            cors_allow_credentials: false,
+            cors_max_age: None,
+            event_compaction_interval: None,
+            event_retention: 1000,
+            blocking_workers: 4,
+            blocking_queue: 16,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            host: env::var("HOST").unwrap_or(defaults.host),
+            port: env::var("PORT").unwrap_or(defaults.port),
+            dev_logging: env::var("DEV_LOGGING").unwrap_or_else(|_| "0".into()) == "1",
+            dedup_window: parse_secs(env::var("DEDUP_WINDOW_SECS").ok()),
+            dedup_max_entries: parse_count(env::var("DEDUP_MAX_ENTRIES").ok()).unwrap_or(defaults.dedup_max_entries),
+            undo_window: parse_secs(env::var("UNDO_WINDOW_SECS").ok()),
+            max_connections: parse_count(env::var("MAX_CONNECTIONS").ok()),
+            cache_max_age: parse_secs(env::var("CACHE_MAX_AGE_SECS").ok()),
+            log_sample_rate: env::var("LOG_SAMPLE_RATE")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.log_sample_rate),
+            log_slow_threshold: env::var("LOG_SLOW_MS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.log_slow_threshold),
+            slow_lock_threshold: env::var("SLOW_LOCK_MS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.slow_lock_threshold),
+            enable_writes: env::var("ENABLE_WRITES").map(|v| v.trim() != "0").unwrap_or(defaults.enable_writes),
+            require_https: env::var("REQUIRE_HTTPS").map(|v| v.trim() == "1").unwrap_or(defaults.require_https),
+            strict_json: env::var("STRICT_JSON").map(|v| v.trim() == "1").unwrap_or(defaults.strict_json),
+            status_case: env::var("STATUS_CASE")
+                .ok()
+                .and_then(|v| StatusCase::parse(&v))
+                .unwrap_or(defaults.status_case),
+            error_format: env::var("ERROR_FORMAT")
+                .ok()
+                .and_then(|v| ErrorFormat::parse(&v))
+                .unwrap_or(defaults.error_format),
+            max_results: env::var("MAX_RESULTS").map(|v| parse_count(Some(v))).unwrap_or(defaults.max_results),
+            list_order: env::var("LIST_DEFAULT_ORDER")
+                .ok()
+                .and_then(|v| ListOrder::parse(&v))
+                .unwrap_or(defaults.list_order),
+            item_normalization: env::var("NORMALIZE_ITEMS")
+                .ok()
+                .and_then(|v| ItemNormalization::parse(&v, &env::var("NORMALIZE_ITEMS_KEEP").unwrap_or_default()))
+                .unwrap_or(defaults.item_normalization),
+            length_limits: LengthLimits {
+                customer: parse_count(env::var("MAX_CUSTOMER_LEN").ok()).unwrap_or(defaults.length_limits.customer),
+                item: parse_count(env::var("MAX_ITEM_LEN").ok()).unwrap_or(defaults.length_limits.item),
+            },
+            fuzzy_max_distance: env::var("FUZZY_MAX_DISTANCE")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.fuzzy_max_distance),
+            latency_budgets: env::var("LATENCY_BUDGETS_MS")
+                .map(|v| LatencyBudgets::parse(&v))
+                .unwrap_or(defaults.latency_budgets),
+            request_timeouts: RouteTimeouts::new(
+                env::var("REQUEST_TIMEOUT_MS")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .map(|ms| Some(Duration::from_millis(ms)).filter(|limit| !limit.is_zero()))
+                    .unwrap_or(defaults.request_timeouts.default),
+                &env::var("REQUEST_TIMEOUTS_MS").unwrap_or_default(),
+            ),
+            cors_origins: env::var("CORS_ORIGINS")
+                .map(|v| v.split(',').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect())
+                .unwrap_or(defaults.cors_origins),
+            cors_allow_credentials: env::var("CORS_ALLOW_CREDENTIALS").map(|v| v.trim() == "1").unwrap_or(defaults.cors_allow_credentials),
+            cors_max_age: parse_secs(env::var("CORS_MAX_AGE_SECS").ok()),
+            event_compaction_interval: parse_secs(env::var("EVENT_COMPACTION_SECS").ok()),
+            event_retention: env::var("EVENT_RETENTION")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.event_retention),
+            blocking_workers: parse_count(env::var("BLOCKING_WORKERS").ok()).unwrap_or(defaults.blocking_workers),
+            blocking_queue: env::var("BLOCKING_QUEUE")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.blocking_queue),
+        }
+    }
+
+    /// Refuses settings that can't be served safely
+    pub fn check(&self) -> Result<(), String> {
+        if self.cors_allow_credentials && self.cors_origins.iter().any(|origin| origin == "*") {
+            return Err("CORS_ORIGINS=* can't be combined with CORS_ALLOW_CREDENTIALS=1; list the allowed origins".into());
+        }
+        Ok(())
+    }
+}
+
+/// Parses a positive number of seconds; zero or garbage means "disabled"
+fn parse_secs(value: Option<String>) -> Option<Duration> {
+    value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Parses a positive count; zero or garbage means "no limit"
+fn parse_count(value: Option<String>) -> Option<usize> {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|count| *count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_secs() {
+        assert_eq!(parse_secs(Some("5".to_string())), Some(Duration::from_secs(5)));
+        assert_eq!(parse_secs(Some(" 30 ".to_string())), Some(Duration::from_secs(30)));
+        assert_eq!(parse_secs(Some("0".to_string())), None);
+        assert_eq!(parse_secs(Some("soon".to_string())), None);
+        assert_eq!(parse_secs(None), None);
+    }
+
+    #[test]
+    fn test_max_results_defaults_to_a_cap() {
+        assert_eq!(Config::default().max_results, Some(1000));
+    }
+
+    #[test]
+    fn test_check_refuses_any_origin_with_credentials() {
+        let mut config = Config { cors_origins: vec!["*".to_string()], ..Config::default() };
+        assert!(config.check().is_ok());
+        config.cors_allow_credentials = true;
+        assert!(config.check().is_err());
+        config.cors_origins = vec!["https://app.example.com".to_string()];
+        assert!(config.check().is_ok());
+    }
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!(parse_count(Some("128".to_string())), Some(128));
+        assert_eq!(parse_count(Some("0".to_string())), None);
+        assert_eq!(parse_count(Some("-1".to_string())), None);
+        assert_eq!(parse_count(None), None);
+    }
+}
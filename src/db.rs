@@ -1,76 +1,971 @@
-use crate::order_dtos::{CreateOrderDto, OrderResponseDto, UpdateStatusDto};
-use crate::models::Order;
+use crate::order_dtos::{AgeStatsDto, AssigneeDto, BulkTagDto, Capped, CreateOrderDto, CreatedRange, DeliveredDto, DiffQuery, FieldChangeDto, HistoryQuery, InstantOrderDto, ItemNormalization, ListOrder, ListQuery, OrderDiffDto, OrderFilter, OrderListDto, OrderPatchDto, OrderResponseDto, OrderTemplateDto, PageDto, SearchQuery, StatusChangeDto, StoreStatsDto, UpdateStatusDto};
+use crate::models::{self, Order};
+use crate::cache::TtlCache;
+use crate::clock::Clock;
+use crate::config::Config;
 use crate::errors::ApiError;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
-use tracing::info;
+use crate::events::{EventLog, EventRecord, EventSnapshot, OrderEvent};
+use chrono::{DateTime, Utc};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, Instant}};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::{info, warn, Span};
 use uuid::Uuid;
 use validator::Validate;
 
-pub type Db = Arc<RwLock<HashMap<Uuid, Order>>>; // Using an in memory hashMap
+/// Shared store handle. Every write finishes under the write lock before the call returns, and the
+/// lock's release/acquire ordering makes it visible to any later read, so a task always sees its own writes.
+///
+/// Lock ordering: an operation touching several orders takes this one lock once and holds it throughout,
+/// never one lock per order, so two bulk operations over overlapping orders cannot deadlock. If orders ever
+/// get their own locks, a multi-order operation must take them in ascending id order, with the store lock
+/// (when it needs it too) taken first.
+pub type Db = Arc<RwLock<Store>>;
 
+/// Waits for the store lock longer than this many microseconds are logged
+static SLOW_LOCK_MICROS: AtomicU64 = AtomicU64::new(100_000);
+
+pub fn set_slow_lock_threshold(threshold: Duration) {
+    SLOW_LOCK_MICROS.store(u64::try_from(threshold.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// Takes the read lock for `op`, warning if another task kept it waiting
+async fn read<'a>(db: &'a Db, op: &'static str) -> RwLockReadGuard<'a, Store> {
+    let started = Instant::now();
+    let guard = db.read().await;
+    warn_if_slow(op, "read", started.elapsed());
+    guard
+}
+
+/// Takes the write lock for `op`, warning if another task kept it waiting
+async fn write<'a>(db: &'a Db, op: &'static str) -> RwLockWriteGuard<'a, Store> {
+    let started = Instant::now();
+    let guard = db.write().await;
+    warn_if_slow(op, "write", started.elapsed());
+    guard
+}
+
+fn warn_if_slow(op: &'static str, lock: &'static str, waited: Duration) {
+    if waited.as_micros() > u128::from(SLOW_LOCK_MICROS.load(Ordering::Relaxed)) {
+        warn!(op, lock, ?waited, "Slow store lock acquisition");
+    }
+}
+
+/// Hands out one shared allocation per distinct item name
+#[derive(Debug, Default)]
+struct ItemInterner(HashSet<Arc<str>>);
+
+impl ItemInterner {
+    fn intern(&mut self, item: &str) -> Arc<str> {
+        if let Some(existing) = self.0.get(item) {
+            return existing.clone();
+        }
+        let item: Arc<str> = Arc::from(item);
+        self.0.insert(item.clone());
+        item
+    }
+
+    fn intern_all<S: AsRef<str>>(&mut self, items: &[S]) -> Vec<Arc<str>> {
+        items.iter().map(|item| self.intern(item.as_ref())).collect()
+    }
+
+    /// Forgets names no stored order refers to any more
+    fn release_unused(&mut self) {
+        self.0.retain(|item| Arc::strong_count(item) > 1);
+    }
+}
+
+/// Customer and items of a recent create, for duplicate detection
+type CreateKey = (String, Vec<Arc<str>>);
+
+/// In memory store: the orders plus bookkeeping guarded by the same lock
+#[derive(Debug, Default)]
+pub struct Store {
+    pub orders: HashMap<Uuid, Order>, // Using an in memory hashMap
+    /// Recent creates; an identical one within the cache's TTL is rejected (None = dedup disabled)
+    recent_creates: Option<TtlCache<CreateKey, Uuid>>,
+    /// Cap on unpaginated result sets (None = uncapped)
+    pub max_results: Option<usize>,
+    /// Order of list results before any `sort`
+    pub list_order: ListOrder,
+    /// Applied to item names before they are stored
+    item_normalization: ItemNormalization,
+    /// Most edits a customer name may be from the query in fuzzy search
+    fuzzy_max_distance: usize,
+    /// How long a deleted order can still be restored (None = deletes are final)
+    undo_window: Option<chrono::Duration>,
+    /// Deleted orders by id, with when they went, while they can still be restored
+    deleted: HashMap<Uuid, (DateTime<Utc>, Order)>,
+    item_names: ItemInterner,
+    /// Saved create bodies by name
+    templates: BTreeMap<String, OrderTemplateDto>,
+    /// Bumped on every successful write, so readers can tell whether anything changed
+    pub version: u64,
+    /// Stamps created_at, updated_at and history entries
+    clock: Box<dyn Clock>,
+    events: EventLog,
+    #[cfg(feature = "search")]
+    search: crate::search::SearchIndex,
+}
+
+impl Store {
+    pub fn new(config: &Config, clock: Box<dyn Clock>) -> Self {
+        Self {
+            recent_creates: config.dedup_window.map(|window| TtlCache::new(window, config.dedup_max_entries)),
+            max_results: config.max_results,
+            list_order: config.list_order,
+            item_normalization: config.item_normalization.clone(),
+            fuzzy_max_distance: config.fuzzy_max_distance,
+            undo_window: config.undo_window.and_then(|window| chrono::Duration::from_std(window).ok()),
+            clock,
+            ..Default::default()
+        }
+    }
+
+    /// Id of an identical order created within the dedup window, remembering this one otherwise
+    fn check_duplicate(&mut self, order: &Order) -> Option<Uuid> {
+        let recent = self.recent_creates.as_mut()?;
+        let now = self.clock.now();
+        let key = (order.customer.clone(), self.item_names.intern_all(&order.items));
+        if let Some(existing) = recent.get(&key, now) {
+            return Some(*existing);
+        }
+        recent.insert(key, order.id, now);
+        None
+    }
+
+    /// Sizes of the store's maps, walked in place without cloning
+    fn stats(&self) -> StoreStatsDto {
+        let order_bytes: usize = self
+            .orders
+            .values()
+            .map(|o| {
+                std::mem::size_of::<Order>()
+                    + o.customer.capacity()
+                    + o.status.capacity()
+                    + o.assignee.as_ref().map_or(0, String::capacity)
+                    + o.items.capacity() * std::mem::size_of::<Arc<str>>()
+                    + o.history.capacity() * std::mem::size_of::<models::StatusChange>()
+                    + o.history.iter().map(|c| c.from.capacity() + c.to.capacity()).sum::<usize>()
+            })
+            .sum();
+        // Item names are shared, so each is counted once
+        let item_bytes: usize = self.item_names.0.iter().map(|item| item.len()).sum();
+        StoreStatsDto {
+            orders: self.orders.len(),
+            approx_bytes: order_bytes + item_bytes,
+            item_names: self.item_names.0.len(),
+            recent_creates: self.recent_creates.as_ref().map_or(0, TtlCache::len),
+            version: self.version,
+        }
+    }
+
+    /// Stores an order, sharing its item names with the orders already stored
+    fn insert(&mut self, mut order: Order) {
+        order.items = self.item_names.intern_all(&order.items);
+        let id = order.id;
+        self.orders.insert(id, order);
+        self.version += 1;
+        self.reindex(id);
+    }
+
+    /// Cleans up after orders were taken out of `orders`, keeping them for the undo window if there is one
+    fn forget(&mut self, removed: Vec<Order>) {
+        if removed.is_empty() {
+            return;
+        }
+        let now = self.clock.now();
+        self.drop_expired_deletes(now);
+        let ids: Vec<Uuid> = removed.iter().map(|order| order.id).collect();
+        for order in removed {
+            self.events.record(OrderEvent::OrderDeleted { order_id: order.id }, now);
+            if self.undo_window.is_some() {
+                self.deleted.insert(order.id, (now, order));
+            }
+        }
+        self.item_names.release_unused();
+        self.version += 1;
+        #[cfg(feature = "search")]
+        if let Err(e) = self.search.remove(&ids) {
+            tracing::error!("Failed to drop {} orders from the search index: {}", ids.len(), e);
+        }
+        #[cfg(not(feature = "search"))]
+        let _ = ids;
+    }
+
+    /// Lets go of deleted orders whose undo window has passed
+    fn drop_expired_deletes(&mut self, now: DateTime<Utc>) {
+        if let Some(window) = self.undo_window {
+            self.deleted.retain(|_, (deleted_at, _)| now - *deleted_at < window);
+        }
+    }
+
+    /// Brings the search index in line with the stored order (no-op without the `search` feature)
+    fn reindex(&mut self, id: Uuid) {
+        #[cfg(feature = "search")]
+        if let Some(order) = self.orders.get(&id)
+            && let Err(e) = self.search.upsert(order)
+        {
+            tracing::error!("Failed to index order {:?}: {}", id, e);
+        }
+        #[cfg(not(feature = "search"))]
+        let _ = id;
+    }
+}
+
+/// Logs the order's fields besides status, after anything but a status change touched them
+fn log_update(events: &mut EventLog, order: &Order) {
+    events.record(OrderEvent::updated(order), order.updated_at);
+}
+
+/// Logs the status change `transition` just applied to the order
+fn log_status_change(events: &mut EventLog, order: &Order) {
+    if let Some(event) = OrderEvent::last_status_change(order) {
+        events.record(event, order.updated_at);
+    }
+}
+
+/// Applies a status change if the transition rules allow it
+fn transition(order: &mut Order, status: String, by: Option<String>, at: DateTime<Utc>) -> Result<(), ApiError> {
+    check_transition(order.frozen, &order.status, &status)?;
+    order.set_status(status, by, at);
+    Ok(())
+}
+
+fn check_transition(frozen: bool, from: &str, to: &str) -> Result<(), ApiError> {
+    if frozen {
+        return Err(ApiError::Conflict("order is frozen".into()));
+    }
+    if !models::can_transition(from, to) {
+        return Err(ApiError::Conflict(format!("cannot move order from {from} to {to}")));
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(order_id = tracing::field::Empty))]
 pub async fn create_order(db: Db, data: CreateOrderDto) -> Result<OrderResponseDto, ApiError> {
     data.validate()?; // validation
-    let order = Order {
-        id: Uuid::new_v4(),
-        customer: data.customer,
-        items: data.items,
-        status: "pending".into(),
-    };
-    {
-        let mut map = db.write().await;
-        map.insert(order.id, order.clone());
-        info!("Inserted order into DB: {:?}", order);
+    let mut store = write(&db, "create_order").await;
+    let items = store.item_normalization.apply_all(data.items);
+    let order = Order::new(data.customer, items, store.clock.now());
+    Span::current().record("order_id", tracing::field::display(order.id));
+    if let Some(existing) = store.check_duplicate(&order) {
+        info!("Rejected duplicate of order {:?}", existing);
+        return Err(ApiError::Duplicate(existing));
     }
+    store.events.record(OrderEvent::created(&order), order.created_at);
+    store.insert(order.clone());
+    info!("Inserted order into DB: {:?}", order);
     Ok(order.into())
 }
 
+/// Creates an order that skips straight from pending to a terminal status
+pub async fn create_instant_order(db: Db, data: InstantOrderDto) -> Result<OrderResponseDto, ApiError> {
+    data.validate()?; // same rules as a normal create, plus the status
+    let status = data.status.unwrap_or_else(|| "shipped".into());
+    let mut store = write(&db, "create_instant_order").await;
+    let now = store.clock.now();
+    let items = store.item_normalization.apply_all(data.items);
+    let mut order = Order::new(data.customer, items, now);
+    transition(&mut order, status, None, now)?;
+    if let Some(existing) = store.check_duplicate(&order) {
+        info!("Rejected duplicate of order {:?}", existing);
+        return Err(ApiError::Duplicate(existing));
+    }
+    store.events.record(OrderEvent::created(&order), order.created_at);
+    log_status_change(&mut store.events, &order);
+    store.insert(order.clone());
+    info!("Inserted instant order into DB: {:?}", order);
+    Ok(order.into())
+}
+
+/// Saves a template under its name, replacing any template already saved under it
+pub async fn save_template(db: Db, template: OrderTemplateDto) -> Result<OrderTemplateDto, ApiError> {
+    template.validate()?; // validation
+    write(&db, "save_template").await.templates.insert(template.name.clone(), template.clone());
+    info!("Saved order template {:?}", template.name);
+    Ok(template)
+}
+
+/// Every saved template, by name
+pub async fn list_templates(db: Db) -> Vec<OrderTemplateDto> {
+    read(&db, "list_templates").await.templates.values().cloned().collect()
+}
+
+/// Creates an order from a saved template, exactly as if its body had been posted to `/orders`
+pub async fn instantiate_template(db: Db, name: &str) -> Result<OrderResponseDto, ApiError> {
+    let order = read(&db, "instantiate_template")
+        .await
+        .templates
+        .get(name)
+        .map(OrderTemplateDto::order)
+        .ok_or(ApiError::NotFound { resource: "template" })?;
+    create_order(db, order).await
+}
+
+/// Current store version; read it before the data it describes so a tag is never newer than its body
+pub async fn store_version(db: Db) -> u64 {
+    read(&db, "store_version").await.version
+}
+
 pub async fn get_order(db: Db, id: Uuid) -> Result<OrderResponseDto, ApiError> {
-    db.read()
+    read(&db, "get_order")
         .await
+        .orders
         .get(&id)
         .cloned()
         .map(OrderResponseDto::from)
-        .ok_or(ApiError::NotFound)
+        .ok_or(ApiError::NotFound { resource: "order" })
 }
 
-pub async fn list_orders(db: Db) -> Vec<OrderResponseDto> {
-    db.read()
-        .await
+/// Matching orders; without an explicit `limit` the list is capped at `max_results`
+pub async fn list_orders(db: Db, query: ListQuery) -> Capped<OrderResponseDto> {
+    list_orders_at(db, query).await.1
+}
+
+/// `list_orders` with the store version the orders were read at
+pub async fn list_orders_at(db: Db, mut query: ListQuery) -> (u64, Capped<OrderResponseDto>) {
+    let max = match query.limit {
+        Some(_) => None,
+        None => read(&db, "list_orders").await.max_results,
+    };
+    // One extra, so a list exactly at the cap is not reported as truncated
+    query.limit = query.limit.or(max.map(|max| max + 1));
+    let page = list_orders_page(db, query).await;
+    (page.version, Capped::new(page.items, max))
+}
+
+/// One page of matching orders plus the total and where the next page starts
+pub async fn list_orders_page(db: Db, query: ListQuery) -> OrderListDto {
+    let item = query.item.as_deref().map(str::to_lowercase);
+    let store = read(&db, "list_orders_page").await;
+    let mut orders: Vec<Order> = store
+        .orders
         .values()
+        .filter(|o| query.status.as_ref().is_none_or(|s| &o.status == s))
+        .filter(|o| query.assignee.is_none() || o.assignee == query.assignee)
+        .filter(|o| item.as_ref().is_none_or(|item| o.items.iter().any(|i| i.to_lowercase() == *item)))
+        .filter(|o| query.created.contains(o.created_at))
         .cloned()
+        .collect();
+    let list_order = store.list_order;
+    let version = store.version;
+    drop(store);
+
+    // HashMap order changes between calls; a fixed base order keeps pages stable and `sort` ties
+    // in a predictable order, as the sorts below are stable
+    match list_order {
+        ListOrder::CreatedAt => orders.sort_by_key(|o| (o.created_at, o.id)),
+        ListOrder::Id => orders.sort_by_key(|o| o.id),
+    }
+
+    if let Some(sort) = &query.sort {
+        let (field, descending) = match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort.as_str(), false),
+        };
+        match field {
+            "customer" => orders.sort_by(|a, b| a.customer.cmp(&b.customer)),
+            _ => orders.sort_by(|a, b| a.status.cmp(&b.status)),
+        }
+        if descending {
+            orders.reverse();
+        }
+    }
+
+    let total = orders.len();
+    let offset = query.offset.unwrap_or(0);
+    let items: Vec<OrderResponseDto> = orders
+        .into_iter()
+        .skip(offset)
+        .take(query.limit.unwrap_or(usize::MAX))
         .map(OrderResponseDto::from)
-        .collect()
+        .collect();
+    let next = offset.saturating_add(items.len());
+    OrderListDto {
+        page: PageDto {
+            limit: query.limit,
+            next_cursor: (next < total).then(|| next.to_string()),
+            total,
+        },
+        items,
+        version,
+    }
+}
+
+/// Status changes of an order in chronological order, filtered and paged by `query`
+pub async fn order_history(db: Db, id: Uuid, query: HistoryQuery) -> Result<Vec<StatusChangeDto>, ApiError> {
+    let store = read(&db, "order_history").await;
+    let order = store.orders.get(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    Ok(order
+        .history
+        .iter()
+        .enumerate()
+        .map(|(index, c)| (index + 1, c))
+        .filter(|(version, _)| query.after_version.is_none_or(|after| *version > after))
+        .filter(|(_, c)| query.after.is_none_or(|after| c.at > after))
+        .filter(|(_, c)| query.to.as_ref().is_none_or(|to| &c.to == to))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|(version, c)| StatusChangeDto::new(version, c.clone()))
+        .collect())
+}
+
+/// What changed between two recorded versions of an order. Only status changes are versioned.
+pub async fn order_diff(db: Db, id: Uuid, query: DiffQuery) -> Result<OrderDiffDto, ApiError> {
+    let store = read(&db, "order_diff").await;
+    let order = store.orders.get(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    let from = order.status_at(query.from).ok_or(ApiError::NotFound { resource: "version" })?;
+    let to = order.status_at(query.to).ok_or(ApiError::NotFound { resource: "version" })?;
+
+    let mut changes = BTreeMap::new();
+    if from != to {
+        changes.insert("status".to_string(), FieldChangeDto {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+    Ok(OrderDiffDto {
+        from: query.from,
+        to: query.to,
+        changes,
+    })
+}
+
+/// Statuses the order may legally move to next
+pub async fn order_transitions(db: Db, id: Uuid) -> Result<&'static [&'static str], ApiError> {
+    let store = read(&db, "order_transitions").await;
+    let order = store.orders.get(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    Ok(models::allowed_transitions(&order.status))
 }
 
 pub async fn update_status(db: Db, id: Uuid, data: UpdateStatusDto) -> Result<OrderResponseDto, ApiError> {
+    change_status(db, id, data, false).await
+}
+
+/// Like `update_status`, but an order already in the requested status is returned unchanged
+/// rather than rejected, for callers that repeatedly assert the state an order should be in
+pub async fn ensure_status(db: Db, id: Uuid, data: UpdateStatusDto) -> Result<OrderResponseDto, ApiError> {
+    change_status(db, id, data, true).await
+}
+
+#[tracing::instrument(name = "update_status", skip(db, data), fields(order_id = %id, status = %data.status))]
+async fn change_status(db: Db, id: Uuid, data: UpdateStatusDto, idempotent: bool) -> Result<OrderResponseDto, ApiError> {
     data.validate()?; // validation
-    let mut map = db.write().await;
-    if let Some(order) = map.get_mut(&id) {
-        order.status = data.status;
+    let mut guard = write(&db, "update_status").await;
+    let store = &mut *guard;
+    if let Some(order) = store.orders.get_mut(&id) {
+        // Checked under the same write lock as the change, so nothing can slip in between
+        if already_applied(&order.status, &data, idempotent)? {
+            return Ok(order.clone().into());
+        }
+        transition(order, data.status, None, store.clock.now())?;
+        log_status_change(&mut store.events, order);
+        store.version += 1;
         info!("Updated order {:?} => status {}", id, order.status);
         return Ok(order.clone().into());
     }
-    Err(ApiError::NotFound)
+    Err(ApiError::NotFound { resource: "order" })
+}
+
+/// Compare-and-set check shared by status changes and their preflight: a status other than
+/// `expected` is a mismatch, and true means `idempotent` makes the change a no-op
+fn already_applied(current: &str, data: &UpdateStatusDto, idempotent: bool) -> Result<bool, ApiError> {
+    if let Some(expected) = data.expected.as_ref().filter(|expected| *expected != current) {
+        return Err(ApiError::StatusMismatch { expected: expected.clone(), actual: current.to_string() });
+    }
+    Ok(idempotent && current == data.status)
+}
+
+/// What `update_status` (or `ensure_status`, when `idempotent`) would say to each change if they
+/// were sent one after another, without applying any
+pub async fn preflight_status(db: Db, updates: Vec<(Uuid, UpdateStatusDto)>, idempotent: bool) -> Vec<Result<(), ApiError>> {
+    let store = read(&db, "preflight_status").await;
+    // Statuses as the earlier changes in the batch would leave them
+    let mut projected: HashMap<Uuid, String> = HashMap::new();
+    updates
+        .into_iter()
+        .map(|(id, data)| {
+            data.validate()?;
+            let order = store.orders.get(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+            let current = projected.get(&id).unwrap_or(&order.status);
+            if !already_applied(current, &data, idempotent)? {
+                check_transition(order.frozen, current, &data.status)?;
+                projected.insert(id, data.status);
+            }
+            Ok(())
+        })
+        .collect()
+}
+
+/// Confirms delivery of a shipped order, recording who delivered it
+pub async fn mark_delivered(db: Db, id: Uuid, data: DeliveredDto) -> Result<OrderResponseDto, ApiError> {
+    data.validate()?; // validation
+    let mut guard = write(&db, "mark_delivered").await;
+    let store = &mut *guard;
+    let order = store.orders.get_mut(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    transition(order, "delivered".into(), data.by, store.clock.now())?;
+    log_status_change(&mut store.events, order);
+    store.version += 1;
+    info!("Order {:?} delivered", id);
+    Ok(order.clone().into())
+}
+
+pub async fn set_assignee(db: Db, id: Uuid, data: AssigneeDto) -> Result<OrderResponseDto, ApiError> {
+    data.validate()?; // validation
+    let mut guard = write(&db, "set_assignee").await;
+    let store = &mut *guard;
+    if let Some(order) = store.orders.get_mut(&id) {
+        order.assignee = data.assignee;
+        order.updated_at = store.clock.now();
+        log_update(&mut store.events, order);
+        store.version += 1;
+        info!("Updated order {:?} => assignee {:?}", id, order.assignee);
+        return Ok(order.clone().into());
+    }
+    Err(ApiError::NotFound { resource: "order" })
+}
+
+/// Fields a merge patch may not null out
+const REQUIRED_FIELDS: &[&str] = &["id", "customer", "items", "status"];
+
+/// Freezes or unfreezes an order; setting the flag it already has changes nothing
+pub async fn set_frozen(db: Db, id: Uuid, frozen: bool) -> Result<OrderResponseDto, ApiError> {
+    let mut guard = write(&db, "set_frozen").await;
+    let store = &mut *guard;
+    let order = store.orders.get_mut(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    if order.frozen != frozen {
+        order.frozen = frozen;
+        order.updated_at = store.clock.now();
+        log_update(&mut store.events, order);
+        store.version += 1;
+        info!("Order {:?} frozen: {}", id, frozen);
+    }
+    Ok(order.clone().into())
+}
+
+/// Fields an update mask may name
+const MASKABLE_FIELDS: &[&str] = &["customer", "items", "status", "assignee"];
+
+/// Fields of an order document, the only ones a strict-mode patch may name
+const ORDER_FIELDS: &[&str] = &["id", "customer", "items", "status", "assignee", "tags", "frozen", "created_at", "updated_at"];
+
+/// Strict JSON: rejects a patch naming a field orders do not have (non-objects are rejected when applied)
+pub fn check_patch_fields(patch: &serde_json::Value) -> Result<(), ApiError> {
+    let unknown = patch.as_object().and_then(|fields| fields.keys().find(|f| !ORDER_FIELDS.contains(&f.as_str())));
+    match unknown {
+        Some(field) => Err(ApiError::BadRequest(format!("unknown field `{field}`"))),
+        None => Ok(()),
+    }
+}
+
+/// Keeps only the patch fields named in the comma separated `mask`; unknown names are rejected
+pub fn apply_update_mask(patch: serde_json::Value, mask: &str) -> Result<serde_json::Value, ApiError> {
+    let fields: Vec<&str> = mask.split(',').map(str::trim).filter(|f| !f.is_empty()).collect();
+    if fields.is_empty() {
+        return Err(ApiError::BadRequest("update_mask must name at least one field".into()));
+    }
+    if let Some(unknown) = fields.iter().find(|f| !MASKABLE_FIELDS.contains(f)) {
+        return Err(ApiError::BadRequest(format!("unknown field in update_mask: {unknown}")));
+    }
+    let serde_json::Value::Object(mut patch) = patch else {
+        return Ok(patch); // merge_patch_order reports the wrong shape
+    };
+    patch.retain(|field, _| fields.contains(&field.as_str()));
+    Ok(serde_json::Value::Object(patch))
+}
+
+/// Applies an RFC 7386 JSON Merge Patch to an order and re-validates the result
+pub async fn merge_patch_order(db: Db, id: Uuid, patch: serde_json::Value) -> Result<OrderResponseDto, ApiError> {
+    let fields = patch
+        .as_object()
+        .ok_or_else(|| ApiError::BadRequest("merge patch must be a JSON object".into()))?;
+    if let Some(field) = REQUIRED_FIELDS.iter().find(|f| fields.get(**f).is_some_and(|v| v.is_null())) {
+        return Err(ApiError::BadRequest(format!("{field} is required and cannot be null")));
+    }
+
+    let mut guard = write(&db, "merge_patch_order").await;
+    let store = &mut *guard;
+    let order = store.orders.get_mut(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+
+    let mut doc = serde_json::to_value(OrderResponseDto::from(order.clone()))?;
+    json_patch::merge(&mut doc, &patch);
+    if doc["id"] != serde_json::json!(id) {
+        return Err(ApiError::BadRequest("id cannot be changed".into()));
+    }
+    let merged: OrderPatchDto = serde_json::from_value(doc).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    merged.validate()?; // validation
+
+    if merged.status != order.status {
+        transition(order, merged.status, None, store.clock.now())?;
+        log_status_change(&mut store.events, order);
+    }
+    order.customer = merged.customer;
+    let items = store.item_normalization.apply_all(merged.items);
+    order.items = store.item_names.intern_all(&items);
+    order.assignee = merged.assignee;
+    order.updated_at = store.clock.now();
+    log_update(&mut store.events, order);
+    let patched = order.clone();
+    store.item_names.release_unused();
+    store.version += 1;
+    store.reindex(id);
+    info!("Patched order {:?}", id);
+    Ok(patched.into())
 }
 
+#[tracing::instrument(skip(db), fields(order_id = %id))]
 pub async fn delete_order(db: Db, id: Uuid) -> Result<(), ApiError> {
-    let mut map = db.write().await;
-    if let Some(_) = map.remove(&id) {
+    let mut store = write(&db, "delete_order").await;
+    if let Some(order) = store.orders.remove(&id) {
+        store.forget(vec![order]);
         info!("Deleted order {:?}", id);
         Ok(())
     } else {
-        Err(ApiError::NotFound)
+        Err(ApiError::NotFound { resource: "order" })
+    }
+}
+
+/// Brings back an order deleted within the undo window; 410 once the window has passed
+#[tracing::instrument(skip(db), fields(order_id = %id))]
+pub async fn undelete_order(db: Db, id: Uuid) -> Result<OrderResponseDto, ApiError> {
+    let mut store = write(&db, "undelete_order").await;
+    let (deleted_at, order) = store.deleted.remove(&id).ok_or(ApiError::NotFound { resource: "order" })?;
+    let now = store.clock.now();
+    if store.undo_window.is_none_or(|window| now - deleted_at >= window) {
+        return Err(ApiError::Gone("the undo window for this order has passed".into()));
+    }
+    store.events.record(OrderEvent::restored(&order), now);
+    store.insert(order.clone());
+    info!("Undeleted order {:?}", id);
+    Ok(order.into())
+}
+
+/// Deletes every order matching the filter under a single write lock, returning how many were removed
+pub async fn bulk_delete(db: Db, filter: OrderFilter) -> Result<usize, ApiError> {
+    if filter.is_empty() {
+        return Err(ApiError::BadRequest("at least one filter field is required".into()));
+    }
+    let mut store = write(&db, "bulk_delete").await;
+    let removed: Vec<Order> = store.orders.extract_if(|_, order| filter.matches(order)).map(|(_, order)| order).collect();
+    let count = removed.len();
+    store.forget(removed);
+    info!("Bulk deleted {} orders", count);
+    Ok(count)
+}
+
+/// Applies tag additions and removals to every order matching the filter under a single write lock,
+/// returning how many orders actually changed
+pub async fn bulk_tag(db: Db, data: BulkTagDto) -> Result<usize, ApiError> {
+    if data.filter.is_empty() {
+        return Err(ApiError::BadRequest("at least one filter field is required".into()));
+    }
+    data.validate()?; // validation
+    let add: Vec<String> = data.add.iter().map(|tag| tag.trim().to_string()).collect();
+    let mut guard = write(&db, "bulk_tag").await;
+    let store = &mut *guard;
+    let now = store.clock.now();
+    let mut modified = 0;
+    for order in store.orders.values_mut().filter(|order| data.filter.matches(order)) {
+        let before = order.tags.clone();
+        order.tags.extend(add.iter().cloned());
+        for tag in &data.remove {
+            order.tags.remove(tag.trim());
+        }
+        if order.tags != before {
+            order.updated_at = now;
+            log_update(&mut store.events, order);
+            modified += 1;
+        }
+    }
+    if modified > 0 {
+        store.version += 1;
+    }
+    info!("Bulk tagged {} orders", modified);
+    Ok(modified)
+}
+
+/// Min/avg/max age per status in a single pass; every known status gets a bucket, even an empty one
+fn age_stats<'a>(orders: impl Iterator<Item = &'a Order>, now: DateTime<Utc>) -> BTreeMap<String, AgeStatsDto> {
+    let mut buckets: BTreeMap<String, (usize, f64, f64, f64)> = models::STATUSES
+        .iter()
+        .map(|s| (s.to_string(), (0, 0.0, f64::INFINITY, f64::NEG_INFINITY)))
+        .collect();
+    for order in orders {
+        let age = (now - order.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        let (count, sum, min, max) = buckets
+            .entry(order.status.clone())
+            .or_insert((0, 0.0, f64::INFINITY, f64::NEG_INFINITY));
+        *count += 1;
+        *sum += age;
+        *min = min.min(age);
+        *max = max.max(age);
+    }
+    buckets
+        .into_iter()
+        .map(|(status, (count, sum, min, max))| {
+            let stats = match count {
+                0 => AgeStatsDto::default(),
+                _ => AgeStatsDto {
+                    count,
+                    min_secs: Some(min),
+                    avg_secs: Some(sum / count as f64),
+                    max_secs: Some(max),
+                },
+            };
+            (status, stats)
+        })
+        .collect()
+}
+
+/// Scan fallback for search: orders ranked by how many of their words match a query term
+#[cfg(any(test, not(feature = "search")))]
+fn scan_ranked<'a>(orders: impl Iterator<Item = &'a Order>, query: &str) -> Vec<Uuid> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut scored: Vec<(usize, &Order)> = orders
+        .map(|order| {
+            let words = std::iter::once(order.customer.as_str())
+                .chain(order.items.iter().map(|item| item.as_ref()))
+                .flat_map(|text| text.split(|c: char| !c.is_alphanumeric()))
+                .filter(|word| terms.iter().any(|term| word.to_lowercase() == *term));
+            (words.count(), order)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.customer.cmp(&b.1.customer)));
+    scored.into_iter().map(|(_, order)| order.id).collect()
+}
+
+/// Orders whose customer name, or one word of it, is within `max_distance` edits of the query, closest first
+fn fuzzy_ranked<'a>(orders: impl Iterator<Item = &'a Order>, query: &str, max_distance: usize) -> Vec<Uuid> {
+    let query = query.trim().to_lowercase();
+    let mut scored: Vec<(usize, &Order)> = orders
+        .filter_map(|order| {
+            let name = order.customer.to_lowercase();
+            let distance = name
+                .split_whitespace()
+                .chain(std::iter::once(name.as_str()))
+                .map(|candidate| strsim::levenshtein(&query, candidate))
+                .min()?;
+            (distance <= max_distance).then_some((distance, order))
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.customer.cmp(&b.1.customer)).then_with(|| a.1.id.cmp(&b.1.id)));
+    scored.into_iter().map(|(_, order)| order.id).collect()
+}
+
+/// Ids of the orders sharing words with the query, most relevant first
+fn text_ranked(store: &Store, query: &str, limit: usize) -> Result<Vec<Uuid>, ApiError> {
+    #[cfg(feature = "search")]
+    let ids = store.search.search(query, limit).map_err(|e| ApiError::internal(format_args!("search for {query:?} failed: {e}")))?;
+    #[cfg(not(feature = "search"))]
+    let ids: Vec<Uuid> = scan_ranked(store.orders.values(), query).into_iter().take(limit).collect();
+    Ok(ids)
+}
+
+/// Orders matching a free-text query over customer and items, most relevant first; with `fuzzy`,
+/// orders whose customer name is close to the query instead
+pub async fn search_orders(db: Db, query: SearchQuery) -> Result<Vec<OrderResponseDto>, ApiError> {
+    query.validate()?; // validation
+    let limit = query.limit.unwrap_or(20);
+    let store = read(&db, "search_orders").await;
+    let ids = if query.fuzzy {
+        fuzzy_ranked(store.orders.values(), &query.q, store.fuzzy_max_distance)
+    } else {
+        text_ranked(&store, &query.q, limit)?
+    };
+    Ok(ids
+        .iter()
+        .take(limit)
+        .filter_map(|id| store.orders.get(id))
+        .cloned()
+        .map(OrderResponseDto::from)
+        .collect())
+}
+
+/// Age statistics per status, computed under the read lock without cloning any order
+pub async fn order_stats(db: Db) -> BTreeMap<String, AgeStatsDto> {
+    let store = read(&db, "order_stats").await;
+    age_stats(store.orders.values(), store.clock.now())
+}
+
+/// Logged events with a sequence number above `since`, oldest first; 410 if compaction trimmed some of them
+pub async fn events_since(db: Db, since: u64) -> Result<Capped<EventRecord>, ApiError> {
+    let store = read(&db, "events_since").await;
+    let events = store.events.since(since).map_err(|through| {
+        ApiError::Gone(format!("events up to {through} were compacted; start from GET /events/snapshot"))
+    })?;
+    Ok(Capped::new(events, store.max_results))
+}
+
+/// The latest compaction snapshot, if the log has been compacted
+pub async fn event_snapshot(db: Db) -> Result<EventSnapshot, ApiError> {
+    let store = read(&db, "event_snapshot").await;
+    store.events.snapshot().cloned().ok_or(ApiError::NotFound { resource: "snapshot" })
+}
+
+/// Snapshots every order and trims the event log down to the `retain` most recent events
+pub async fn compact_events(db: Db, retain: usize) {
+    let mut guard = write(&db, "compact_events").await;
+    let store = &mut *guard;
+    let mut orders: Vec<&Order> = store.orders.values().collect();
+    orders.sort_by_key(|order| (order.created_at, order.id));
+    let orders = orders.into_iter().cloned().map(OrderResponseDto::from).collect();
+    store.events.compact(orders, store.clock.now(), retain);
+    info!("Compacted the event log, keeping {} events", retain);
+}
+
+/// Replays the event log into fresh orders and swaps them in, returning how many there are. The swap only
+/// happens when the replay reproduces the live store exactly; otherwise it is 409 and nothing changes.
+pub async fn rebuild_from_events(db: Db) -> Result<usize, ApiError> {
+    let mut guard = write(&db, "rebuild_from_events").await;
+    let store = &mut *guard;
+    let rebuilt = store.events.replay();
+    let differing: BTreeSet<Uuid> = store
+        .orders
+        .keys()
+        .chain(rebuilt.keys())
+        .filter(|id| store.orders.get(id) != rebuilt.get(id))
+        .copied()
+        .collect();
+    if let Some(first) = differing.first() {
+        return Err(ApiError::Conflict(format!(
+            "the event log does not reproduce {} orders (e.g. {first}), so the rebuild was not applied",
+            differing.len()
+        )));
+    }
+    let count = rebuilt.len();
+    store.orders.clear();
+    for order in rebuilt.into_values() {
+        store.insert(order);
+    }
+    store.item_names.release_unused();
+    info!("Rebuilt {} orders from the event log", count);
+    Ok(count)
+}
+
+/// Runs `compact_events` every `interval` in the background
+pub fn spawn_event_compaction(db: Db, interval: Duration, retain: usize) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; there is nothing to compact at startup
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            compact_events(db.clone(), retain).await;
+        }
+    })
+}
+
+pub async fn store_stats(db: Db) -> StoreStatsDto {
+    read(&db, "store_stats").await.stats()
+}
+
+/// Ids of every stored order, used to stream backups without cloning the whole store
+pub async fn order_ids(db: Db) -> Vec<Uuid> {
+    read(&db, "order_ids").await.orders.keys().copied().collect()
+}
+
+/// Every order created within `range`, oldest first, copied out so the lock is released before heavy work on them
+pub async fn snapshot_orders(db: Db, range: CreatedRange) -> Vec<Order> {
+    snapshot_orders_at(db, range).await.1
+}
+
+/// `snapshot_orders` with the store version the orders were read at
+pub async fn snapshot_orders_at(db: Db, range: CreatedRange) -> (u64, Vec<Order>) {
+    let store = read(&db, "snapshot_orders").await;
+    let mut orders: Vec<Order> = store.orders.values().filter(|order| range.contains(order.created_at)).cloned().collect();
+    let version = store.version;
+    drop(store);
+    orders.sort_by_key(|order| (order.created_at, order.id));
+    (version, orders)
+}
+
+/// Raw domain order for backups (None if it was deleted in the meantime)
+pub async fn export_order(db: Db, id: Uuid) -> Option<Order> {
+    read(&db, "export_order").await.orders.get(&id).cloned()
+}
+
+/// Inserts a previously backed up order, keeping its id and status
+pub async fn restore_order(db: Db, order: Order) -> Result<(), ApiError> {
+    check_restorable(&order)?;
+    let mut store = write(&db, "restore_order").await;
+    info!("Restored order {:?}", order.id);
+    let now = store.clock.now();
+    store.events.record(OrderEvent::restored(&order), now);
+    store.insert(order);
+    Ok(())
+}
+
+/// Inserts already checked backed up orders under a single write lock, returning the ids that
+/// replaced an order already in the store
+pub async fn restore_orders(db: Db, orders: Vec<Order>) -> Vec<Uuid> {
+    let mut store = write(&db, "restore_orders").await;
+    let count = orders.len();
+    let now = store.clock.now();
+    let mut overwritten = Vec::new();
+    for order in orders {
+        if store.orders.contains_key(&order.id) {
+            overwritten.push(order.id);
+        }
+        store.events.record(OrderEvent::restored(&order), now);
+        store.insert(order);
+    }
+    info!("Restored {} orders, {} of them over existing ones", count, overwritten.len());
+    overwritten
+}
+
+/// A backed up order must pass the same rules as a create and carry a known status
+pub fn check_restorable(order: &Order) -> Result<(), ApiError> {
+    CreateOrderDto {
+        customer: order.customer.clone(),
+        items: order.items.iter().map(|item| item.to_string()).collect(),
     }
+    .validate()?;
+    UpdateStatusDto {
+        status: order.status.clone(),
+        expected: None,
+    }
+    .validate()?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use std::time::Duration;
+    use crate::clock::MockClock;
+    use std::collections::BTreeSet;
 
     fn create_test_db() -> Db {
-        Arc::new(RwLock::new(HashMap::new()))
+        Arc::new(RwLock::new(Store::default()))
+    }
+
+    fn create_dedup_db(clock: &MockClock) -> Db {
+        let config = Config { dedup_window: Some(Duration::from_secs(60)), ..Config::default() };
+        Arc::new(RwLock::new(Store::new(&config, Box::new(clock.clone()))))
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_come_from_the_clock() {
+        let start = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().to_utc();
+        let clock = MockClock::new(start);
+        let db = Arc::new(RwLock::new(Store::new(&Config::default(), Box::new(clock.clone()))));
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let order = create_order(db.clone(), dto).await.unwrap();
+        assert_eq!(order.created_at, start);
+        assert_eq!(order.updated_at, start);
+
+        clock.advance(chrono::Duration::minutes(5));
+        let status = UpdateStatusDto { status: "shipped".to_string(), expected: None };
+        let order = update_status(db.clone(), order.id, status).await.unwrap();
+        assert_eq!(order.created_at, start);
+        assert_eq!(order.updated_at, start + chrono::Duration::minutes(5));
+
+        clock.advance(chrono::Duration::minutes(1));
+        let stats = order_stats(db).await;
+        assert_eq!(stats["shipped"].max_secs, Some(60.0));
     }
 
     #[tokio::test]
@@ -102,6 +997,58 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_create_order_rapid_duplicate_rejected() {
+        let clock = MockClock::new(Utc::now());
+        let db = create_dedup_db(&clock);
+        let dto = || CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        let first = create_order(db.clone(), dto()).await.unwrap();
+        let result = create_order(db.clone(), dto()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Duplicate(id) if id == first.id));
+        assert_eq!(db.read().await.orders.len(), 1);
+
+        // A different order from the same customer is not a duplicate
+        let other = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 2".to_string()],
+        };
+        assert!(create_order(db, other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_create_order_duplicate_after_window_allowed() {
+        let clock = MockClock::new(Utc::now());
+        let db = create_dedup_db(&clock);
+        let dto = || CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        create_order(db.clone(), dto()).await.unwrap();
+        clock.advance(chrono::Duration::seconds(59));
+        assert!(matches!(create_order(db.clone(), dto()).await, Err(ApiError::Duplicate(_))));
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(create_order(db.clone(), dto()).await.is_ok());
+        assert_eq!(db.read().await.orders.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_order_no_dedup_by_default() {
+        let db = create_test_db();
+        let dto = || CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        create_order(db.clone(), dto()).await.unwrap();
+        assert!(create_order(db.clone(), dto()).await.is_ok());
+        assert_eq!(db.read().await.orders.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_get_order() {
         let db = create_test_db();
@@ -124,7 +1071,7 @@ mod tests {
 
         let result = get_order(db, non_existent_id).await;
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), ApiError::NotFound));
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
     }
 
     #[tokio::test]
@@ -132,7 +1079,7 @@ mod tests {
         let db = create_test_db();
 
         // Initially empty
-        let orders = list_orders(db.clone()).await;
+        let orders = list_orders(db.clone(), ListQuery::default()).await.items;
         assert_eq!(orders.len(), 0);
 
         // Add some orders
@@ -148,43 +1095,94 @@ mod tests {
         create_order(db.clone(), dto1).await.unwrap();
         create_order(db.clone(), dto2).await.unwrap();
 
-        let orders = list_orders(db).await;
+        let orders = list_orders(db, ListQuery::default()).await.items;
         assert_eq!(orders.len(), 2);
     }
 
     #[tokio::test]
-    async fn test_update_status() {
-        let db = create_test_db();
-        let dto = CreateOrderDto {
-            customer: "Test Customer".to_string(),
-            items: vec!["Item 1".to_string()],
-        };
+    async fn test_list_orders_order_is_deterministic() {
+        let clock = MockClock::new(Utc::now());
+        let db = Arc::new(RwLock::new(Store::new(&Config::default(), Box::new(clock.clone()))));
+        let mut created = Vec::new();
+        for i in 0..8 {
+            let dto = CreateOrderDto {
+                customer: format!("Customer {}", i % 3),
+                items: vec![format!("Item {i}")],
+            };
+            created.push(create_order(db.clone(), dto).await.unwrap().id);
+            // Pairs share a timestamp, so ties are broken by id
+            if i % 2 == 1 {
+                clock.advance(chrono::Duration::seconds(1));
+            }
+        }
+        let ids = |orders: Capped<OrderResponseDto>| orders.items.into_iter().map(|o| o.id).collect::<Vec<_>>();
 
-        let created_order = create_order(db.clone(), dto).await.unwrap();
-        let update_dto = UpdateStatusDto {
-            status: "shipped".to_string(),
-        };
+        let first = ids(list_orders(db.clone(), ListQuery::default()).await);
+        assert_eq!(first, ids(list_orders(db.clone(), ListQuery::default()).await));
+        for pair in created.chunks_mut(2) {
+            pair.sort();
+        }
+        assert_eq!(first, created);
 
-        let updated_order = update_status(db, created_order.id, update_dto).await.unwrap();
-        assert_eq!(updated_order.status, "shipped");
-        assert_eq!(updated_order.id, created_order.id);
+        db.write().await.list_order = ListOrder::Id;
+        let by_id = ids(list_orders(db.clone(), ListQuery::default()).await);
+        created.sort();
+        assert_eq!(by_id, created);
+
+        // Equal customers keep the base order under a sort
+        let query = || ListQuery { sort: Some("customer".to_string()), ..Default::default() };
+        assert_eq!(ids(list_orders(db.clone(), query()).await), ids(list_orders(db, query()).await));
     }
 
     #[tokio::test]
-    async fn test_update_status_not_found() {
+    async fn test_list_orders_with_query() {
         let db = create_test_db();
-        let non_existent_id = Uuid::new_v4();
+        for customer in ["Carol", "Alice", "Bob"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Item 1".to_string()],
+            };
+            create_order(db.clone(), dto).await.unwrap();
+        }
+        let bob = list_orders(db.clone(), ListQuery::default())
+            .await
+            .items
+            .into_iter()
+            .find(|o| o.customer == "Bob")
+            .unwrap();
         let update_dto = UpdateStatusDto {
             status: "shipped".to_string(),
+            expected: None,
         };
+        update_status(db.clone(), bob.id, update_dto).await.unwrap();
 
-        let result = update_status(db, non_existent_id, update_dto).await;
-        assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), ApiError::NotFound));
+        // Filter by status
+        let query = ListQuery {
+            status: Some("pending".to_string()),
+            ..Default::default()
+        };
+        let orders = list_orders(db.clone(), query).await.items;
+        assert_eq!(orders.len(), 2);
+        assert!(orders.iter().all(|o| o.status == "pending"));
+
+        // Sort descending and paginate
+        let query = ListQuery {
+            sort: Some("-customer".to_string()),
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let customers: Vec<String> = list_orders(db, query)
+            .await
+            .items
+            .into_iter()
+            .map(|o| o.customer)
+            .collect();
+        assert_eq!(customers, vec!["Bob", "Alice"]);
     }
 
     #[tokio::test]
-    async fn test_update_status_validation_error() {
+    async fn test_update_status() {
         let db = create_test_db();
         let dto = CreateOrderDto {
             customer: "Test Customer".to_string(),
@@ -192,16 +1190,543 @@ mod tests {
         };
 
         let created_order = create_order(db.clone(), dto).await.unwrap();
-        let invalid_update_dto = UpdateStatusDto {
-            status: "invalid_status".to_string(),
+        let update_dto = UpdateStatusDto {
+            status: "shipped".to_string(),
+            expected: None,
         };
 
-        let result = update_status(db, created_order.id, invalid_update_dto).await;
-        assert!(result.is_err());
+        let updated_order = update_status(db.clone(), created_order.id, update_dto).await.unwrap();
+        assert_eq!(updated_order.status, "shipped");
+        assert_eq!(updated_order.id, created_order.id);
+
+        let history = db.read().await.orders[&created_order.id].history.clone();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from, "pending");
+        assert_eq!(history[0].to, "shipped");
     }
 
-    #[tokio::test]
-    async fn test_delete_order() {
+    async fn create_order_with_history(db: Db, statuses: &[&str]) -> Uuid {
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let order = create_order(db.clone(), dto).await.unwrap();
+        for status in statuses {
+            let update_dto = UpdateStatusDto {
+                status: status.to_string(),
+                expected: None,
+            };
+            update_status(db.clone(), order.id, update_dto).await.unwrap();
+        }
+        order.id
+    }
+
+    #[tokio::test]
+    async fn test_order_history_limit_and_after() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+
+        let all = order_history(db.clone(), id, HistoryQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 2);
+
+        let query = HistoryQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = order_history(db.clone(), id, query).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].to, "shipped");
+
+        // Next page starts after the last entry seen
+        let query = HistoryQuery {
+            limit: Some(1),
+            after_version: Some(page[0].version),
+            ..Default::default()
+        };
+        let next_page = order_history(db.clone(), id, query).await.unwrap();
+        assert_eq!(next_page.len(), 1);
+        assert_eq!(next_page[0].to, "delivered");
+        assert_eq!(next_page[0].version, 2);
+
+        let query = HistoryQuery {
+            after: Some(page[0].at),
+            ..Default::default()
+        };
+        assert_eq!(order_history(db, id, query).await.unwrap()[0].to, "delivered");
+    }
+
+    #[tokio::test]
+    async fn test_order_history_pages_across_equal_timestamps() {
+        // A clock that never moves gives every change the same timestamp
+        let clock = MockClock::new(Utc::now());
+        let db = undo_db(&clock);
+        let id = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+
+        let mut seen = Vec::new();
+        let mut after_version = None;
+        loop {
+            let query = HistoryQuery { limit: Some(1), after_version, ..Default::default() };
+            let page = order_history(db.clone(), id, query).await.unwrap();
+            let Some(last) = page.last() else { break };
+            after_version = Some(last.version);
+            seen.extend(page.into_iter().map(|c| c.to));
+        }
+        assert_eq!(seen, ["shipped", "delivered"]);
+    }
+
+    #[tokio::test]
+    async fn test_order_history_filter_by_status() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+
+        let query = HistoryQuery {
+            to: Some("delivered".to_string()),
+            ..Default::default()
+        };
+        let entries = order_history(db, id, query).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].from, "shipped");
+    }
+
+    #[tokio::test]
+    async fn test_order_history_not_found() {
+        let db = create_test_db();
+        let result = order_history(db, Uuid::new_v4(), HistoryQuery::default()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_create_instant_order() {
+        let db = create_test_db();
+        let dto = InstantOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+            status: None,
+        };
+
+        let order = create_instant_order(db.clone(), dto).await.unwrap();
+        assert_eq!(order.status, "shipped");
+
+        let history = db.read().await.orders[&order.id].history.clone();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from, "pending");
+        assert_eq!(history[0].to, "shipped");
+    }
+
+    #[tokio::test]
+    async fn test_create_instant_order_with_status() {
+        let db = create_test_db();
+        let dto = InstantOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+            status: Some("cancelled".to_string()),
+        };
+
+        let order = create_instant_order(db, dto).await.unwrap();
+        assert_eq!(order.status, "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_create_instant_order_illegal_status() {
+        let db = create_test_db();
+        let dto = InstantOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+            status: Some("delivered".to_string()),
+        };
+
+        let result = create_instant_order(db.clone(), dto).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+        assert!(db.read().await.orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_instant_order_validation_error() {
+        let db = create_test_db();
+        let dto = InstantOrderDto {
+            customer: "".to_string(),
+            items: vec!["Item 1".to_string()],
+            status: None,
+        };
+
+        let result = create_instant_order(db, dto).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Validation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_update_status_not_found() {
+        let db = create_test_db();
+        let non_existent_id = Uuid::new_v4();
+        let update_dto = UpdateStatusDto {
+            status: "shipped".to_string(),
+            expected: None,
+        };
+
+        let result = update_status(db, non_existent_id, update_dto).await;
+        assert!(result.is_err());
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_update_status_illegal_transition() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        let created_order = create_order(db.clone(), dto).await.unwrap();
+        let update_dto = UpdateStatusDto {
+            status: "delivered".to_string(),
+            expected: None,
+        };
+
+        let result = update_status(db.clone(), created_order.id, update_dto).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+        assert_eq!(get_order(db, created_order.id).await.unwrap().status, "pending");
+    }
+
+    #[tokio::test]
+    async fn test_preflight_matches_applying_each_change() {
+        let db = create_test_db();
+        let a = create_order_with_history(db.clone(), &[]).await;
+        let b = create_order_with_history(db.clone(), &[]).await;
+        let frozen = create_order_with_history(db.clone(), &[]).await;
+        set_frozen(db.clone(), frozen, true).await.unwrap();
+        let batch = [
+            (a, "shipped", None),
+            (a, "delivered", Some("shipped")), // legal only because the change above ran first
+            (a, "cancelled", Some("shipped")), // stale: the change above left it delivered
+            (b, "delivered", None),
+            (b, "cancelled", None),
+            (frozen, "shipped", None),
+            (Uuid::new_v4(), "shipped", None),
+            (a, "lost", None),
+        ];
+        let updates = || {
+            batch
+                .iter()
+                .map(|(id, status, expected)| (*id, UpdateStatusDto { status: status.to_string(), expected: expected.map(String::from) }))
+                .collect::<Vec<_>>()
+        };
+
+        let report = preflight_status(db.clone(), updates(), false).await;
+        assert_eq!(report.iter().map(Result::is_ok).collect::<Vec<_>>(), [true, true, false, false, true, false, false, false]);
+        assert!(matches!(&report[2], Err(ApiError::StatusMismatch { actual, .. }) if actual == "delivered"));
+        // Nothing was applied
+        assert_eq!(get_order(db.clone(), a).await.unwrap().status, "pending");
+
+        for ((id, data), preflight) in updates().into_iter().zip(report) {
+            let applied = update_status(db.clone(), id, data).await;
+            assert_eq!(
+                applied.map(|_| ()).map_err(|e| e.to_string()),
+                preflight.map_err(|e| e.to_string())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_preflight_matches_ensure_status() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped"]).await;
+        let updates = || vec![(id, UpdateStatusDto { status: "shipped".to_string(), expected: None })];
+
+        assert!(preflight_status(db.clone(), updates(), false).await[0].is_err());
+        let report = preflight_status(db.clone(), updates(), true).await;
+        assert!(report[0].is_ok());
+        let (id, data) = updates().remove(0);
+        assert!(ensure_status(db, id, data).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mark_delivered_lifecycle() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped"]).await;
+
+        let dto = DeliveredDto {
+            by: Some("courier-7".to_string()),
+        };
+        let order = mark_delivered(db.clone(), id, dto).await.unwrap();
+        assert_eq!(order.status, "delivered");
+
+        let history = order_history(db, id, HistoryQuery::default()).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].from, "shipped");
+        assert_eq!(history[1].to, "delivered");
+        assert_eq!(history[1].by.as_deref(), Some("courier-7"));
+    }
+
+    #[tokio::test]
+    async fn test_mark_delivered_rejects_skip_from_pending() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+
+        let result = mark_delivered(db.clone(), id, DeliveredDto::default()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+        assert_eq!(get_order(db, id).await.unwrap().status, "pending");
+    }
+
+    #[tokio::test]
+    async fn test_mark_delivered_not_found() {
+        let db = create_test_db();
+        let result = mark_delivered(db, Uuid::new_v4(), DeliveredDto::default()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_update_status_validation_error() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        let created_order = create_order(db.clone(), dto).await.unwrap();
+        let invalid_update_dto = UpdateStatusDto {
+            status: "invalid_status".to_string(),
+            expected: None,
+        };
+
+        let result = update_status(db, created_order.id, invalid_update_dto).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_clear_assignee() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let created_order = create_order(db.clone(), dto).await.unwrap();
+
+        let assign = AssigneeDto {
+            assignee: Some("alice".to_string()),
+        };
+        let order = set_assignee(db.clone(), created_order.id, assign).await.unwrap();
+        assert_eq!(order.assignee.as_deref(), Some("alice"));
+
+        let clear = AssigneeDto { assignee: None };
+        let order = set_assignee(db.clone(), created_order.id, clear).await.unwrap();
+        assert!(order.assignee.is_none());
+        assert!(get_order(db, created_order.id).await.unwrap().assignee.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_assignee_not_found() {
+        let db = create_test_db();
+        let assign = AssigneeDto {
+            assignee: Some("alice".to_string()),
+        };
+        let result = set_assignee(db, Uuid::new_v4(), assign).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_list_orders_by_assignee() {
+        let db = create_test_db();
+        for (customer, assignee) in [("Alice", Some("alice")), ("Bob", Some("bob")), ("Carol", None)] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Item 1".to_string()],
+            };
+            let order = create_order(db.clone(), dto).await.unwrap();
+            let assign = AssigneeDto {
+                assignee: assignee.map(str::to_string),
+            };
+            set_assignee(db.clone(), order.id, assign).await.unwrap();
+        }
+
+        let query = ListQuery {
+            assignee: Some("alice".to_string()),
+            ..Default::default()
+        };
+        let orders = list_orders(db, query).await.items;
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].customer, "Alice");
+    }
+
+    #[tokio::test]
+    async fn test_list_orders_page() {
+        let db = create_test_db();
+        for _ in 0..3 {
+            create_order_with_history(db.clone(), &[]).await;
+        }
+
+        let query = ListQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = list_orders_page(db.clone(), query).await;
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.page.total, 3);
+        assert_eq!(page.page.next_cursor.as_deref(), Some("2"));
+
+        let query = ListQuery {
+            limit: Some(2),
+            offset: Some(2),
+            ..Default::default()
+        };
+        let page = list_orders_page(db, query).await;
+        assert_eq!(page.items.len(), 1);
+        assert!(page.page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_orders_by_item() {
+        let db = create_test_db();
+        for (customer, items) in [("Alice", vec!["Widget", "Gadget"]), ("Bob", vec!["widget"]), ("Carol", vec!["Widgets"])] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: items.into_iter().map(str::to_string).collect(),
+            };
+            create_order(db.clone(), dto).await.unwrap();
+        }
+        let bob = list_orders(db.clone(), ListQuery::default()).await.items.into_iter().find(|o| o.customer == "Bob").unwrap();
+        update_status(db.clone(), bob.id, UpdateStatusDto { status: "shipped".to_string(), expected: None }).await.unwrap();
+
+        let query = ListQuery {
+            item: Some("WIDGET".to_string()),
+            sort: Some("customer".to_string()),
+            ..Default::default()
+        };
+        let customers: Vec<String> = list_orders(db.clone(), query).await.items.into_iter().map(|o| o.customer).collect();
+        assert_eq!(customers, ["Alice", "Bob"]);
+
+        // Combined with other filters via AND
+        let query = ListQuery {
+            item: Some("widget".to_string()),
+            status: Some("pending".to_string()),
+            ..Default::default()
+        };
+        let orders = list_orders(db, query).await.items;
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].customer, "Alice");
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_sets_field() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+
+        let patch = serde_json::json!({ "customer": "New Name", "assignee": "alice" });
+        let order = merge_patch_order(db, id, patch).await.unwrap();
+        assert_eq!(order.customer, "New Name");
+        assert_eq!(order.assignee.as_deref(), Some("alice"));
+        assert_eq!(order.items, vec!["Item 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_null_clears_optional_field() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+        let assign = AssigneeDto {
+            assignee: Some("alice".to_string()),
+        };
+        set_assignee(db.clone(), id, assign).await.unwrap();
+
+        let patch = serde_json::json!({ "assignee": null });
+        let order = merge_patch_order(db, id, patch).await.unwrap();
+        assert!(order.assignee.is_none());
+        assert_eq!(order.customer, "Test Customer");
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_empty_is_noop() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+        let before = get_order(db.clone(), id).await.unwrap();
+
+        let order = merge_patch_order(db, id, serde_json::json!({})).await.unwrap();
+        assert_eq!(order.customer, before.customer);
+        assert_eq!(order.items, before.items);
+        assert_eq!(order.status, before.status);
+        assert_eq!(order.assignee, before.assignee);
+    }
+
+    #[tokio::test]
+    async fn test_frozen_order_rejects_status_changes() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+        let ship = || UpdateStatusDto { status: "shipped".to_string(), expected: None };
+
+        let order = set_frozen(db.clone(), id, true).await.unwrap();
+        assert!(order.frozen);
+        let version = store_version(db.clone()).await;
+        set_frozen(db.clone(), id, true).await.unwrap();
+        assert_eq!(store_version(db.clone()).await, version);
+
+        let result = update_status(db.clone(), id, ship()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+        let result = merge_patch_order(db.clone(), id, serde_json::json!({ "status": "cancelled" })).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+        // Other fields can still be edited
+        merge_patch_order(db.clone(), id, serde_json::json!({ "customer": "Renamed" })).await.unwrap();
+
+        set_frozen(db.clone(), id, false).await.unwrap();
+        let order = update_status(db.clone(), id, ship()).await.unwrap();
+        assert_eq!(order.status, "shipped");
+        let result = mark_delivered(db.clone(), id, DeliveredDto::default()).await;
+        assert!(result.is_ok());
+
+        let result = set_frozen(db, Uuid::new_v4(), true).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_update_mask_applies_only_listed_fields() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+
+        let patch = serde_json::json!({ "status": "shipped", "customer": "Ignored", "id": Uuid::new_v4() });
+        let masked = apply_update_mask(patch, "status").unwrap();
+        assert_eq!(masked, serde_json::json!({ "status": "shipped" }));
+
+        let order = merge_patch_order(db, id, masked).await.unwrap();
+        assert_eq!(order.status, "shipped");
+        assert_eq!(order.customer, "Test Customer");
+    }
+
+    #[test]
+    fn test_update_mask_rejects_unknown_or_empty_fields() {
+        let patch = serde_json::json!({ "status": "shipped" });
+        assert!(matches!(apply_update_mask(patch.clone(), "status,price"), Err(ApiError::BadRequest(_))));
+        assert!(matches!(apply_update_mask(patch.clone(), "id"), Err(ApiError::BadRequest(_))));
+        assert!(matches!(apply_update_mask(patch.clone(), " , "), Err(ApiError::BadRequest(_))));
+        assert!(apply_update_mask(patch, " status , customer ").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_rejects_invalid_results() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+
+        let result = merge_patch_order(db.clone(), id, serde_json::json!({ "customer": null })).await;
+        assert!(matches!(result.unwrap_err(), ApiError::BadRequest(_)));
+
+        let result = merge_patch_order(db.clone(), id, serde_json::json!({ "items": [] })).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Validation(_)));
+
+        let result = merge_patch_order(db.clone(), id, serde_json::json!({ "status": "delivered" })).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Conflict(_)));
+
+        let result = merge_patch_order(db.clone(), id, serde_json::json!({ "id": Uuid::new_v4() })).await;
+        assert!(matches!(result.unwrap_err(), ApiError::BadRequest(_)));
+
+        let result = merge_patch_order(db.clone(), id, serde_json::json!(["not", "an", "object"])).await;
+        assert!(matches!(result.unwrap_err(), ApiError::BadRequest(_)));
+
+        let result = merge_patch_order(db.clone(), Uuid::new_v4(), serde_json::json!({})).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+
+        // Nothing was changed by the rejected patches
+        let order = get_order(db, id).await.unwrap();
+        assert_eq!(order.customer, "Test Customer");
+        assert_eq!(order.status, "pending");
+    }
+
+    #[tokio::test]
+    async fn test_delete_order() {
         let db = create_test_db();
         let dto = CreateOrderDto {
             customer: "Test Customer".to_string(),
@@ -215,7 +1740,689 @@ mod tests {
         // Verify order is deleted
         let get_result = get_order(db, created_order.id).await;
         assert!(get_result.is_err());
-        assert!(matches!(get_result.unwrap_err(), ApiError::NotFound));
+        assert!(matches!(get_result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_items_title_cased_when_enabled() {
+        let dto = || CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec![" widget pro".to_string(), "usb hub".to_string()],
+        };
+        let plain = create_order(Arc::new(RwLock::new(Store::default())), dto()).await.unwrap();
+        assert_eq!(plain.items, [" widget pro", "usb hub"]);
+
+        let config = Config {
+            item_normalization: ItemNormalization::parse("titlecase", "USB").unwrap(),
+            ..Default::default()
+        };
+        let db = Arc::new(RwLock::new(Store::new(&config, Box::new(MockClock::new(Utc::now())))));
+        let created = create_order(db.clone(), dto()).await.unwrap();
+        assert_eq!(created.items, ["Widget Pro", "USB Hub"]);
+
+        let patched = merge_patch_order(db, created.id, serde_json::json!({ "items": ["widget pro max"] })).await.unwrap();
+        assert_eq!(patched.items, ["Widget Pro Max"]);
+    }
+
+    fn undo_db(clock: &MockClock) -> Db {
+        let config = Config { undo_window: Some(Duration::from_secs(60)), ..Default::default() };
+        Arc::new(RwLock::new(Store::new(&config, Box::new(clock.clone()))))
+    }
+
+    #[tokio::test]
+    async fn test_undelete_within_window() {
+        let clock = MockClock::new(Utc::now());
+        let db = undo_db(&clock);
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let created = create_order(db.clone(), dto).await.unwrap();
+        delete_order(db.clone(), created.id).await.unwrap();
+
+        clock.advance(chrono::Duration::seconds(59));
+        let restored = undelete_order(db.clone(), created.id).await.unwrap();
+        assert_eq!(restored.id, created.id);
+        assert_eq!(get_order(db.clone(), created.id).await.unwrap().items, ["Item 1"]);
+
+        // Only deleted orders can be restored
+        let again = undelete_order(db, created.id).await;
+        assert!(matches!(again, Err(ApiError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_undelete_after_window_is_gone() {
+        let clock = MockClock::new(Utc::now());
+        let db = undo_db(&clock);
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let created = create_order(db.clone(), dto).await.unwrap();
+        bulk_delete(db.clone(), OrderFilter { status: Some("pending".to_string()), ..Default::default() }).await.unwrap();
+
+        clock.advance(chrono::Duration::seconds(60));
+        let result = undelete_order(db.clone(), created.id).await;
+        assert!(matches!(result, Err(ApiError::Gone(_))));
+        assert!(get_order(db, created.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_undelete_without_window() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+        let created = create_order(db.clone(), dto).await.unwrap();
+        delete_order(db.clone(), created.id).await.unwrap();
+
+        assert!(matches!(undelete_order(db, created.id).await, Err(ApiError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_export_and_restore_order() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Test Customer".to_string(),
+            items: vec!["Item 1".to_string()],
+        };
+
+        let created_order = create_order(db.clone(), dto).await.unwrap();
+        assert_eq!(order_ids(db.clone()).await, vec![created_order.id]);
+
+        let exported = export_order(db.clone(), created_order.id).await.unwrap();
+        let restored_db = create_test_db();
+        restore_order(restored_db.clone(), exported).await.unwrap();
+
+        let restored = get_order(restored_db, created_order.id).await.unwrap();
+        assert_eq!(restored.customer, created_order.customer);
+        assert_eq!(restored.status, created_order.status);
+    }
+
+    #[tokio::test]
+    async fn test_restore_order_validation_error() {
+        let db = create_test_db();
+        let mut invalid_order = Order::new("Test Customer".to_string(), vec!["Item 1".to_string()], Utc::now());
+        invalid_order.status = "lost".to_string();
+
+        let result = restore_order(db.clone(), invalid_order).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Validation(_)));
+        assert!(order_ids(db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_by_status() {
+        let db = create_test_db();
+        let shipped = create_order_with_history(db.clone(), &["shipped"]).await;
+        let pending = create_order_with_history(db.clone(), &[]).await;
+        let cancelled = create_order_with_history(db.clone(), &["cancelled"]).await;
+
+        let filter = OrderFilter {
+            status: Some("shipped".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(bulk_delete(db.clone(), filter).await.unwrap(), 1);
+
+        assert!(get_order(db.clone(), shipped).await.is_err());
+        assert!(get_order(db.clone(), pending).await.is_ok());
+        assert!(get_order(db, cancelled).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_by_date_cutoff() {
+        let db = create_test_db();
+        let old = create_order_with_history(db.clone(), &[]).await;
+        let older = create_order_with_history(db.clone(), &[]).await;
+        let recent = create_order_with_history(db.clone(), &[]).await;
+        {
+            let mut store = db.write().await;
+            store.orders.get_mut(&old).unwrap().created_at = "2024-01-10T00:00:00Z".parse().unwrap();
+            store.orders.get_mut(&older).unwrap().created_at = "2023-06-01T00:00:00Z".parse().unwrap();
+        }
+
+        let filter = OrderFilter {
+            created_before: Some("2024-02-01T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(bulk_delete(db.clone(), filter).await.unwrap(), 2);
+        assert_eq!(order_ids(db).await, vec![recent]);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_tag_by_status() {
+        let db = create_test_db();
+        let shipped = create_order_with_history(db.clone(), &["shipped"]).await;
+        let also_shipped = create_order_with_history(db.clone(), &["shipped"]).await;
+        let pending = create_order_with_history(db.clone(), &[]).await;
+        let tag = |add: &[&str], remove: &[&str]| BulkTagDto {
+            filter: OrderFilter {
+                status: Some("shipped".to_string()),
+                ..Default::default()
+            },
+            add: add.iter().map(|t| t.to_string()).collect(),
+            remove: remove.iter().map(|t| t.to_string()).collect(),
+        };
+
+        assert_eq!(bulk_tag(db.clone(), tag(&["promo", "summer"], &[])).await.unwrap(), 2);
+        assert_eq!(get_order(db.clone(), shipped).await.unwrap().tags, BTreeSet::from(["promo".into(), "summer".into()]));
+        assert!(get_order(db.clone(), pending).await.unwrap().tags.is_empty());
+
+        // Unchanged orders are not counted
+        assert_eq!(bulk_tag(db.clone(), tag(&["promo"], &[])).await.unwrap(), 0);
+        assert_eq!(bulk_tag(db.clone(), tag(&[], &["summer"])).await.unwrap(), 2);
+        assert_eq!(get_order(db.clone(), also_shipped).await.unwrap().tags, BTreeSet::from(["promo".into()]));
+
+        // Tags can then drive other bulk operations
+        let by_tag = OrderFilter {
+            tag: Some("promo".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(bulk_delete(db.clone(), by_tag).await.unwrap(), 2);
+
+        let empty = BulkTagDto {
+            filter: OrderFilter::default(),
+            add: vec!["promo".to_string()],
+            remove: vec![],
+        };
+        assert!(matches!(bulk_tag(db, empty).await.unwrap_err(), ApiError::BadRequest(_)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_overlapping_bulk_operations_do_not_deadlock() {
+        let db = create_test_db();
+        // Two overlapping sets: orders 0..6 carry "left", orders 2..8 carry "right"
+        for i in 0..8 {
+            let id = create_order_with_history(db.clone(), &[]).await;
+            let mut store = db.write().await;
+            let tags = &mut store.orders.get_mut(&id).unwrap().tags;
+            if i < 6 {
+                tags.insert("left".to_string());
+            }
+            if i >= 2 {
+                tags.insert("right".to_string());
+            }
+        }
+        let toggle = |over: &str, tag: &str, add: bool| BulkTagDto {
+            filter: OrderFilter {
+                tag: Some(over.to_string()),
+                ..Default::default()
+            },
+            add: if add { vec![tag.to_string()] } else { vec![] },
+            remove: if add { vec![] } else { vec![tag.to_string()] },
+        };
+
+        let rounds = (0..200).map(|round| {
+            let (db, add) = (db.clone(), round % 2 == 0);
+            let (left, right) = (toggle("left", "seen", add), toggle("right", "seen", !add));
+            tokio::spawn(async move { tokio::join!(bulk_tag(db.clone(), left), bulk_tag(db, right)) })
+        });
+        let done = tokio::time::timeout(Duration::from_secs(10), futures_util::future::join_all(rounds)).await;
+        for round in done.expect("overlapping bulk operations deadlocked") {
+            let (left, right) = round.unwrap();
+            assert!(left.is_ok() && right.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_requires_filter() {
+        let db = create_test_db();
+        create_order_with_history(db.clone(), &[]).await;
+
+        let result = bulk_delete(db.clone(), OrderFilter::default()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::BadRequest(_)));
+        assert_eq!(db.read().await.orders.len(), 1);
+    }
+
+    #[test]
+    fn test_age_stats_per_status() {
+        let now: DateTime<Utc> = "2024-03-01T12:00:00Z".parse().unwrap();
+        let aged = |status: &str, secs: i64| {
+            let mut order = Order::new("John Doe".to_string(), vec!["Item 1".to_string()], Utc::now());
+            order.status = status.to_string();
+            order.updated_at = now - chrono::Duration::seconds(secs);
+            order
+        };
+        let orders = [aged("pending", 60), aged("pending", 180), aged("pending", 300), aged("shipped", 30)];
+
+        let stats = age_stats(orders.iter(), now);
+        assert_eq!(stats["pending"], AgeStatsDto {
+            count: 3,
+            min_secs: Some(60.0),
+            avg_secs: Some(180.0),
+            max_secs: Some(300.0),
+        });
+        assert_eq!(stats["shipped"].avg_secs, Some(30.0));
+        // Empty buckets are reported without ages instead of dividing by zero
+        assert_eq!(stats["delivered"], AgeStatsDto::default());
+        assert_eq!(stats["cancelled"].count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_order_stats_counts_orders() {
+        let db = create_test_db();
+        create_order_with_history(db.clone(), &[]).await;
+        create_order_with_history(db.clone(), &["shipped"]).await;
+
+        let stats = order_stats(db).await;
+        assert_eq!(stats["pending"].count, 1);
+        assert_eq!(stats["shipped"].count, 1);
+        assert!(stats["pending"].avg_secs.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_identical_items_share_allocation() {
+        let db = create_test_db();
+        let first = create_order_with_history(db.clone(), &[]).await;
+        let second = create_order_with_history(db.clone(), &[]).await;
+
+        let store = db.read().await;
+        let a = &store.orders[&first].items[0];
+        let b = &store.orders[&second].items[0];
+        assert_eq!(a.as_ref(), "Item 1");
+        assert!(Arc::ptr_eq(a, b));
+        drop(store);
+
+        // Names stop being tracked once no order uses them
+        delete_order(db.clone(), first).await.unwrap();
+        delete_order(db.clone(), second).await.unwrap();
+        assert!(db.read().await.item_names.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_order_diff() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+
+        let diff = order_diff(db.clone(), id, DiffQuery { from: 0, to: 2 }).await.unwrap();
+        assert_eq!(diff.changes["status"], FieldChangeDto {
+            from: "pending".to_string(),
+            to: "delivered".to_string(),
+        });
+
+        let diff = order_diff(db.clone(), id, DiffQuery { from: 1, to: 1 }).await.unwrap();
+        assert!(diff.changes.is_empty());
+
+        let result = order_diff(db.clone(), id, DiffQuery { from: 0, to: 3 }).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { resource: "version" }));
+        let result = order_diff(db, Uuid::new_v4(), DiffQuery { from: 0, to: 1 }).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { resource: "order" }));
+    }
+
+    #[tokio::test]
+    async fn test_order_transitions() {
+        let db = create_test_db();
+        let pending = create_order_with_history(db.clone(), &[]).await;
+        let delivered = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+
+        assert_eq!(order_transitions(db.clone(), pending).await.unwrap(), ["shipped", "cancelled"]);
+        assert!(order_transitions(db.clone(), delivered).await.unwrap().is_empty());
+        let result = order_transitions(db, Uuid::new_v4()).await;
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
+    }
+
+    /// Order statuses as far as the events tell, starting from `statuses`
+    fn replay(mut statuses: BTreeMap<Uuid, String>, events: &[EventRecord]) -> BTreeMap<Uuid, String> {
+        for record in events {
+            match &record.event {
+                OrderEvent::OrderCreated { order_id, .. } => {
+                    statuses.insert(*order_id, "pending".to_string());
+                }
+                OrderEvent::StatusChanged { order_id, to, .. } => {
+                    statuses.insert(*order_id, to.clone());
+                }
+                OrderEvent::OrderDeleted { order_id } => {
+                    statuses.remove(order_id);
+                }
+                OrderEvent::OrderUpdated { .. } => {}
+                OrderEvent::OrderRestored { order_id, order } => {
+                    statuses.insert(*order_id, order.status.clone());
+                }
+            }
+        }
+        statuses
+    }
+
+    #[tokio::test]
+    async fn test_compaction_trims_events_behind_a_snapshot() {
+        let db = create_test_db();
+        let mut ids = Vec::new();
+        for _ in 0..20 {
+            ids.push(create_order_with_history(db.clone(), &["shipped"]).await);
+        }
+        for id in &ids[..5] {
+            delete_order(db.clone(), *id).await.unwrap();
+        }
+        // 20 creates, 20 status changes, 5 deletes
+        compact_events(db.clone(), 10).await;
+
+        let snapshot = event_snapshot(db.clone()).await.unwrap();
+        assert_eq!(snapshot.seq, 45);
+        assert_eq!(snapshot.orders.len(), 15);
+        assert!(matches!(events_since(db.clone(), 0).await, Err(ApiError::Gone(_))));
+        assert_eq!(events_since(db.clone(), 35).await.unwrap().items.len(), 10);
+
+        // Snapshot plus the events after it gives the current state
+        let status = UpdateStatusDto { status: "delivered".to_string(), expected: None };
+        update_status(db.clone(), ids[5], status).await.unwrap();
+        delete_order(db.clone(), ids[6]).await.unwrap();
+        create_order_with_history(db.clone(), &[]).await;
+
+        let base = snapshot.orders.iter().map(|o| (o.id, o.status.clone())).collect();
+        let replayed = replay(base, &events_since(db.clone(), snapshot.seq).await.unwrap().items);
+        let current: BTreeMap<Uuid, String> = db.read().await.orders.values().map(|o| (o.id, o.status.clone())).collect();
+        assert_eq!(replayed, current);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_from_events_reproduces_the_store() {
+        let clock = MockClock::new(Utc::now());
+        let db = undo_db(&clock);
+        let mut ids = Vec::new();
+        for statuses in [&[][..], &["shipped"], &["shipped", "delivered"], &["cancelled"]] {
+            ids.push(create_order_with_history(db.clone(), statuses).await);
+            clock.advance(chrono::Duration::seconds(1));
+        }
+        delete_order(db.clone(), ids[0]).await.unwrap();
+        delete_order(db.clone(), ids[1]).await.unwrap();
+        undelete_order(db.clone(), ids[1]).await.unwrap();
+        let instant = InstantOrderDto {
+            customer: "Dana".to_string(),
+            items: vec!["Lamp".to_string()],
+            status: None,
+        };
+        create_instant_order(db.clone(), instant).await.unwrap();
+        let before = db.read().await.orders.clone();
+
+        assert_eq!(rebuild_from_events(db.clone()).await.unwrap(), 4);
+        assert_eq!(db.read().await.orders, before);
+
+        // Every other kind of write is logged too
+        set_assignee(db.clone(), ids[2], AssigneeDto { assignee: Some("alice".to_string()) }).await.unwrap();
+        clock.advance(chrono::Duration::seconds(1));
+        let tags = BulkTagDto {
+            filter: OrderFilter { status: Some("delivered".to_string()), ..Default::default() },
+            add: vec!["promo".to_string()],
+            remove: Vec::new(),
+        };
+        bulk_tag(db.clone(), tags).await.unwrap();
+        clock.advance(chrono::Duration::seconds(1));
+        set_frozen(db.clone(), ids[3], true).await.unwrap();
+        let patch = serde_json::json!({ "customer": "Erin", "items": ["Desk"], "status": "shipped" });
+        merge_patch_order(db.clone(), ids[1], patch).await.unwrap();
+        let backup = Order::new("Fay".to_string(), vec!["Mug".to_string()], clock.now());
+        restore_order(db.clone(), backup).await.unwrap();
+        let before = db.read().await.orders.clone();
+
+        assert_eq!(rebuild_from_events(db.clone()).await.unwrap(), 5);
+        assert_eq!(db.read().await.orders, before);
+    }
+
+    #[tokio::test]
+    async fn test_version_bumped_on_writes() {
+        let db = create_test_db();
+        assert_eq!(store_version(db.clone()).await, 0);
+
+        let id = create_order_with_history(db.clone(), &["shipped"]).await;
+        assert_eq!(store_version(db.clone()).await, 2);
+
+        // Failed writes and reads leave it alone
+        let _ = update_status(db.clone(), id, UpdateStatusDto { status: "pending".to_string(), expected: None }).await;
+        get_order(db.clone(), id).await.unwrap();
+        assert_eq!(store_version(db.clone()).await, 2);
+
+        delete_order(db.clone(), id).await.unwrap();
+        assert_eq!(store_version(db).await, 3);
+    }
+
+    /// Span name and recorded fields, keyed by span id
+    type CapturedSpans = HashMap<u64, (&'static str, HashMap<String, String>)>;
+
+    /// Collects the fields of every span opened while it is the default subscriber
+    #[derive(Clone, Default)]
+    struct SpanCapture(Arc<std::sync::Mutex<CapturedSpans>>);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanCapture {
+        fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, id: &tracing::span::Id, _: tracing_subscriber::layer::Context<'_, S>) {
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().insert(id.into_u64(), (attrs.metadata().name(), fields));
+        }
+
+        fn on_record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+            if let Some((_, fields)) = self.0.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+    }
+
+    /// Collects the fields of every event logged while it is the default subscriber
+    #[derive(Clone, Default)]
+    struct EventCapture(Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for EventCapture {
+        fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+            let mut fields = HashMap::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    impl SpanCapture {
+        fn fields_of(&self, name: &str) -> Vec<HashMap<String, String>> {
+            self.0.lock().unwrap().values().filter(|(n, _)| *n == name).map(|(_, f)| f.clone()).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spans_carry_order_id() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = SpanCapture::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped"]).await;
+
+        let updates = capture.fields_of("update_status");
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0]["order_id"], id.to_string());
+        assert_eq!(updates[0]["status"], "shipped");
+        // The id of a new order is only known once it is built, and is recorded then
+        assert_eq!(capture.fields_of("create_order")[0]["order_id"], id.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_slow_lock_acquisition_warns() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = EventCapture::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        set_slow_lock_threshold(Duration::from_millis(20));
+
+        let db = create_test_db();
+        let held = db.write().await;
+        let waiting = tokio::spawn(get_order(db.clone(), Uuid::new_v4()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(held);
+        assert!(waiting.await.unwrap().is_err());
+        // Uncontended, so no warning
+        store_version(db).await;
+
+        let events = capture.0.lock().unwrap();
+        let slow: Vec<_> = events.iter().filter(|e| e["message"] == "Slow store lock acquisition").collect();
+        assert_eq!(slow.len(), 1, "{events:?}");
+        // String fields are captured through Debug, hence the quotes
+        assert_eq!(slow[0]["op"], "\"get_order\"");
+        assert_eq!(slow[0]["lock"], "\"read\"");
+    }
+
+    #[tokio::test]
+    async fn test_store_stats() {
+        let db = create_test_db();
+        for _ in 0..3 {
+            create_order_with_history(db.clone(), &[]).await;
+        }
+
+        let stats = store_stats(db).await;
+        assert_eq!(stats.orders, 3);
+        assert_eq!(stats.item_names, 1);
+        assert_eq!(stats.version, 3);
+        assert!(stats.approx_bytes >= 3 * std::mem::size_of::<Order>());
+    }
+
+    #[test]
+    fn test_scan_ranked() {
+        let jane = Order::new("Jane Doe".to_string(), vec!["red widget".to_string()], Utc::now());
+        let john = Order::new("John".to_string(), vec!["Widget".to_string()], Utc::now());
+        let carol = Order::new("Carol".to_string(), vec!["lamp".to_string()], Utc::now());
+        let orders = [john.clone(), carol, jane.clone()];
+
+        assert_eq!(scan_ranked(orders.iter(), "jane widget"), [jane.id, john.id]);
+        assert!(scan_ranked(orders.iter(), "sofa").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fuzzy_search_tolerates_typos() {
+        let db = Arc::new(RwLock::new(Store::new(&Config::default(), Box::new(MockClock::new(Utc::now())))));
+        for customer in ["John Smith", "Jane Smithers", "Carol"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Widget".to_string()],
+            };
+            create_order(db.clone(), dto).await.unwrap();
+        }
+        let search = |q: &str, fuzzy: bool| SearchQuery { q: q.to_string(), limit: None, fuzzy };
+        let customers = |found: Vec<OrderResponseDto>| found.into_iter().map(|o| o.customer).collect::<Vec<_>>();
+
+        assert!(search_orders(db.clone(), search("smyth", false)).await.unwrap().is_empty());
+        assert_eq!(customers(search_orders(db.clone(), search("smyth", true)).await.unwrap()), ["John Smith"]);
+        // Closest first, whole names count too
+        assert_eq!(
+            customers(search_orders(db.clone(), search("Jane Smither", true)).await.unwrap()),
+            ["Jane Smithers"]
+        );
+        assert_eq!(customers(search_orders(db, search("smithe", true)).await.unwrap()), ["John Smith", "Jane Smithers"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_orders_follows_writes() {
+        let db = create_test_db();
+        for (customer, item) in [("Jane Doe", "Widget"), ("John Smith", "Widget"), ("Carol", "Lamp")] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec![item.to_string()],
+            };
+            create_order(db.clone(), dto).await.unwrap();
+        }
+        let search = |q: &str| SearchQuery { q: q.to_string(), limit: None, fuzzy: false };
+
+        let found = search_orders(db.clone(), search("jane widget")).await.unwrap();
+        let customers: Vec<&str> = found.iter().map(|o| o.customer.as_str()).collect();
+        assert_eq!(customers, ["Jane Doe", "John Smith"]);
+
+        // Patches and deletes are reflected
+        merge_patch_order(db.clone(), found[1].id, serde_json::json!({ "items": ["Sofa"] })).await.unwrap();
+        delete_order(db.clone(), found[0].id).await.unwrap();
+        assert!(search_orders(db.clone(), search("widget")).await.unwrap().is_empty());
+        assert_eq!(search_orders(db.clone(), search("sofa")).await.unwrap().len(), 1);
+
+        let result = search_orders(db, search("")).await;
+        assert!(matches!(result.unwrap_err(), ApiError::Validation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_status_is_a_no_op_in_the_current_status() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &["shipped", "delivered"]).await;
+        let delivered = || UpdateStatusDto { status: "delivered".to_string(), expected: None };
+        let version = store_version(db.clone()).await;
+
+        assert!(matches!(update_status(db.clone(), id, delivered()).await.unwrap_err(), ApiError::Conflict(_)));
+        let order = ensure_status(db.clone(), id, delivered()).await.unwrap();
+        assert_eq!(order.status, "delivered");
+        assert_eq!(order_history(db.clone(), id, HistoryQuery::default()).await.unwrap().len(), 2);
+        assert_eq!(store_version(db.clone()).await, version);
+
+        // Anything the lifecycle forbids is still rejected
+        let dto = UpdateStatusDto { status: "shipped".to_string(), expected: None };
+        assert!(matches!(ensure_status(db.clone(), id, dto).await.unwrap_err(), ApiError::Conflict(_)));
+        assert_eq!(get_order(db, id).await.unwrap().status, "delivered");
+    }
+
+    #[tokio::test]
+    async fn test_update_status_compare_and_set() {
+        let db = create_test_db();
+        let id = create_order_with_history(db.clone(), &[]).await;
+
+        let dto = UpdateStatusDto {
+            status: "shipped".to_string(),
+            expected: Some("pending".to_string()),
+        };
+        assert_eq!(update_status(db.clone(), id, dto).await.unwrap().status, "shipped");
+
+        // A second writer that still thinks the order is pending loses
+        let dto = UpdateStatusDto {
+            status: "cancelled".to_string(),
+            expected: Some("pending".to_string()),
+        };
+        match update_status(db.clone(), id, dto).await.unwrap_err() {
+            ApiError::StatusMismatch { expected, actual } => {
+                assert_eq!(expected, "pending");
+                assert_eq!(actual, "shipped");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(get_order(db, id).await.unwrap().status, "shipped");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_read_your_writes_under_contention() {
+        let db = create_test_db();
+        let (created_tx, mut created_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let writers: Vec<_> = (0..8)
+            .map(|task| {
+                let db = db.clone();
+                let created_tx = created_tx.clone();
+                tokio::spawn(async move {
+                    for n in 0..50 {
+                        let dto = CreateOrderDto {
+                            customer: format!("customer {task}-{n}"),
+                            items: vec!["Item 1".to_string()],
+                        };
+                        let id = create_order(db.clone(), dto).await.unwrap().id;
+                        // Same task, straight after the create
+                        assert!(get_order(db.clone(), id).await.is_ok(), "task {task} lost its own order {id}");
+                        created_tx.send(id).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(created_tx);
+
+        // Another task reads each order as soon as it hears about it
+        let reader = tokio::spawn(async move {
+            let mut seen = 0;
+            while let Some(id) = created_rx.recv().await {
+                assert!(get_order(db.clone(), id).await.is_ok(), "order {id} not visible to another task");
+                seen += 1;
+            }
+            seen
+        });
+
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert_eq!(reader.await.unwrap(), 400);
     }
 
     #[tokio::test]
@@ -225,6 +2432,6 @@ mod tests {
 
         let result = delete_order(db, non_existent_id).await;
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), ApiError::NotFound));
+        assert!(matches!(result.unwrap_err(), ApiError::NotFound { .. }));
     }
 }
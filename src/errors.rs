@@ -1,22 +1,109 @@
-use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use axum::{extract::{Request, State}, http::{header, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
 use serde::Serialize;
+use std::any::Any;
 use thiserror::Error;
-use validator::ValidationErrors;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::i18n;
 
 #[derive(Error, Debug)]
 pub enum ApiError {
-    #[error("Order not found")]
-    NotFound,
+    #[error("{} not found", capitalize(.resource))]
+    NotFound { resource: &'static str },
     #[error("Invalid input: {0}")]
     BadRequest(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Order is {actual}, expected {expected}")]
+    StatusMismatch { expected: String, actual: String },
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Gone: {0}")]
+    Gone(String),
+    #[error("Duplicate of order {0}")]
+    Duplicate(Uuid),
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    /// A `Range` this many bytes long can't be served
+    #[error("Range not satisfiable for {length} bytes")]
+    RangeNotSatisfiable { length: usize },
     #[error("Validation failed")]
     Validation(#[from] ValidationErrors),
+    /// Carries what went wrong for the log; clients only ever see the generic message
     #[error("Internal server error")]
-    Internal,
+    Internal(String),
+}
+
+impl ApiError {
+    /// Internal error with `context` describing the cause
+    pub fn internal(context: impl std::fmt::Display) -> Self {
+        Self::Internal(context.to_string())
+    }
+}
+
+/// For serializing our own data; unparseable client JSON is a `BadRequest` and is mapped explicitly
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::internal(format_args!("JSON serialization failed: {e}"))
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// How validation failures lay out their `details`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// `{"customer": ["..."], "items": ["..."]}`
+    #[default]
+    Map,
+    /// `[{"field": "customer", "message": "..."}, ...]`, one entry per message, sorted by field
+    Flat,
+}
+
+impl ErrorFormat {
+    /// `map` or `flat`; anything else is None
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "map" => Some(Self::Map),
+            "flat" => Some(Self::Flat),
+            _ => None,
+        }
+    }
+}
+
+tokio::task_local! {
+    static FORMAT: ErrorFormat;
+}
+
+/// Middleware making the configured error format apply to errors rendered while the request is handled
+pub async fn use_format(State(format): State<ErrorFormat>, request: Request, next: Next) -> Response {
+    FORMAT.scope(format, next.run(request)).await
+}
+
+/// Error format for the request currently being handled
+pub fn current_format() -> ErrorFormat {
+    FORMAT.try_with(|format| *format).unwrap_or_default()
+}
+
+/// Runs `f` with `format` as the current error format, for errors rendered after the middleware returned
+pub async fn with_format<F: Future>(format: ErrorFormat, f: F) -> F::Output {
+    FORMAT.scope(format, f).await
 }
 
+/// Error body. `code` is machine readable: `bad_request` (400) means the input
+/// could not be parsed, `validation_failed` (422) means it parsed but broke a rule.
 #[derive(Serialize)]
 struct ErrorResponse<T: Serialize> {
+    code: String,
     message: String,
     details: Option<T>
 }
@@ -24,43 +111,127 @@ struct ErrorResponse<T: Serialize> {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
-            ApiError::NotFound => {
+            ApiError::NotFound { resource } => {
                 let body = Json(ErrorResponse::<()> {
-                    message: "Order not found".into(),
+                    code: format!("{resource}_not_found"),
+                    message: format!("{} not found", capitalize(resource)),
                     details: None,
                 });
                 (StatusCode::NOT_FOUND, body).into_response()
             }
             ApiError::BadRequest(msg) => {
                 let body = Json(ErrorResponse::<()> {
+                    code: "bad_request".into(),
                     message: format!("Invalid input: {msg}"),
                     details: None,
                 });
                 (StatusCode::BAD_REQUEST, body).into_response()
             }
+            ApiError::Conflict(msg) => {
+                let body = Json(ErrorResponse::<()> {
+                    code: "conflict".into(),
+                    message: format!("Conflict: {msg}"),
+                    details: None,
+                });
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            ApiError::StatusMismatch { expected, actual } => {
+                let body = Json(ErrorResponse {
+                    code: "status_mismatch".into(),
+                    message: format!("Order is {actual}, expected {expected}"),
+                    details: Some(serde_json::json!({ "expected": expected, "actual": actual })),
+                });
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            ApiError::Forbidden(msg) => {
+                let body = Json(ErrorResponse::<()> {
+                    code: "forbidden".into(),
+                    message: format!("Forbidden: {msg}"),
+                    details: None,
+                });
+                (StatusCode::FORBIDDEN, body).into_response()
+            }
+            ApiError::Gone(msg) => {
+                let body = Json(ErrorResponse::<()> {
+                    code: "gone".into(),
+                    message: format!("Gone: {msg}"),
+                    details: None,
+                });
+                (StatusCode::GONE, body).into_response()
+            }
+            ApiError::Duplicate(existing_id) => {
+                let body = Json(ErrorResponse {
+                    code: "duplicate".into(),
+                    message: "Duplicate order".into(),
+                    details: Some(serde_json::json!({ "existing_id": existing_id })),
+                });
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            ApiError::Unavailable(msg) => {
+                let body = Json(ErrorResponse::<()> {
+                    code: "unavailable".into(),
+                    message: format!("Service unavailable: {msg}"),
+                    details: None,
+                });
+                (StatusCode::SERVICE_UNAVAILABLE, [(header::RETRY_AFTER, "1")], body).into_response()
+            }
+            ApiError::PayloadTooLarge(msg) => {
+                let body = Json(ErrorResponse::<()> {
+                    code: "payload_too_large".into(),
+                    message: format!("Payload too large: {msg}"),
+                    details: None,
+                });
+                (StatusCode::PAYLOAD_TOO_LARGE, body).into_response()
+            }
+            ApiError::RangeNotSatisfiable { length } => {
+                let body = Json(ErrorResponse {
+                    code: "range_not_satisfiable".into(),
+                    message: format!("Range not satisfiable for {length} bytes"),
+                    details: Some(serde_json::json!({ "length": length })),
+                });
+                let content_range = format!("bytes */{length}");
+                (StatusCode::RANGE_NOT_SATISFIABLE, [(header::CONTENT_RANGE, content_range)], body).into_response()
+            }
             ApiError::Validation(errs) => {
-                let details = errs
+                let lang = i18n::current();
+                let mut fields: Vec<(String, Vec<String>)> = errs
                     .field_errors()
                     .iter()
                     .map(|(field, errors)| {
                         let messages: Vec<String> = errors
                             .iter()
-                            .filter_map(|e| e.message.as_ref().map(|m| m.to_string()))
+                            .filter_map(|e| {
+                                i18n::translate(&e.code, lang)
+                                    .map(|template| fill_params(template, e))
+                                    .or_else(|| e.message.as_ref().map(|m| m.to_string()))
+                            })
                             .collect();
-                        (field.to_string(), serde_json::Value::Array(
-                            messages.into_iter().map(serde_json::Value::String).collect()
-                        ))
+                        (field.to_string(), messages)
                     })
-                    .collect::<serde_json::Map<String, _>>();
+                    .collect();
+                fields.sort();
+                let details = match FORMAT.try_with(|format| *format).unwrap_or_default() {
+                    ErrorFormat::Map => serde_json::json!(fields.into_iter().collect::<std::collections::BTreeMap<_, _>>()),
+                    ErrorFormat::Flat => fields
+                        .into_iter()
+                        .flat_map(|(field, messages)| {
+                            messages.into_iter().map(move |message| serde_json::json!({ "field": field, "message": message }))
+                        })
+                        .collect(),
+                };
 
                 let body = Json(ErrorResponse {
+                    code: "validation_failed".into(),
                     message: "Validation failed".into(),
                     details: Some(details),
                 });
-                (StatusCode::BAD_REQUEST, body).into_response()
+                (StatusCode::UNPROCESSABLE_ENTITY, body).into_response()
             }
-            ApiError::Internal => {
+            ApiError::Internal(context) => {
+                // Logged inside the request span, so the line carries the request id
+                tracing::error!("Internal error: {}", context);
                 let body = Json(ErrorResponse::<()> {
+                    code: "internal".into(),
                     message: "Internal server error".into(),
                     details: None,
                 });
@@ -70,6 +241,37 @@ impl IntoResponse for ApiError {
     }
 }
 
+/// Substitutes `{name}` placeholders in a message with the error's params
+fn fill_params(template: &str, error: &ValidationError) -> String {
+    error.params.iter().fold(template.to_string(), |message, (name, value)| {
+        let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+        message.replace(&format!("{{{name}}}"), &value)
+    })
+}
+
+/// Text of a caught panic, when it was raised with a string message
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+/// 500 for a handler that panicked. The panic is logged; its message only reaches the body when `expose` is set.
+pub fn panic_response(payload: Box<dyn Any + Send>, expose: bool) -> Response {
+    let message = panic_message(payload.as_ref()).unwrap_or("non-string panic payload");
+    if !expose {
+        return ApiError::internal(format_args!("handler panicked: {message}")).into_response();
+    }
+    tracing::error!("Handler panicked: {}", message);
+    let body = Json(ErrorResponse {
+        code: "internal".into(),
+        message: "Internal server error".into(),
+        details: Some(serde_json::json!({ "panic": message })),
+    });
+    (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,27 +280,147 @@ mod tests {
 
     #[test]
     fn test_api_error_display() {
-        assert_eq!(ApiError::NotFound.to_string(), "Order not found");
+        assert_eq!(ApiError::NotFound { resource: "order" }.to_string(), "Order not found");
+        assert_eq!(ApiError::NotFound { resource: "route" }.to_string(), "Route not found");
         assert_eq!(ApiError::BadRequest("test".to_string()).to_string(), "Invalid input: test");
-        assert_eq!(ApiError::Internal.to_string(), "Internal server error");
+        assert_eq!(ApiError::Conflict("test".to_string()).to_string(), "Conflict: test");
+        assert_eq!(ApiError::internal("disk on fire").to_string(), "Internal server error");
     }
 
     #[test]
     fn test_not_found_response() {
-        let response = ApiError::NotFound.into_response();
+        let response = ApiError::NotFound { resource: "order" }.into_response();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn test_not_found_names_resource() {
+        let (status, json) = body_json(ApiError::NotFound { resource: "route" }).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["code"], "route_not_found");
+        assert_eq!(json["message"], "Route not found");
+    }
+
     #[test]
     fn test_bad_request_response() {
         let response = ApiError::BadRequest("test error".to_string()).into_response();
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
+    #[test]
+    fn test_conflict_response() {
+        let response = ApiError::Conflict("test conflict".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_status_mismatch_response() {
+        let error = ApiError::StatusMismatch {
+            expected: "pending".to_string(),
+            actual: "shipped".to_string(),
+        };
+        let (status, json) = body_json(error).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["code"], "status_mismatch");
+        assert_eq!(json["details"]["actual"], "shipped");
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_response() {
+        let (status, json) = body_json(ApiError::Forbidden("writes are disabled".to_string())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(json["code"], "forbidden");
+        assert_eq!(json["message"], "Forbidden: writes are disabled");
+    }
+
+    #[tokio::test]
+    async fn test_gone_response() {
+        let (status, json) = body_json(ApiError::Gone("too late".to_string())).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert_eq!(json["code"], "gone");
+        assert_eq!(json["message"], "Gone: too late");
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_response() {
+        let response = ApiError::Unavailable("busy".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let (_, json) = body_json(ApiError::Unavailable("busy".to_string())).await;
+        assert_eq!(json["code"], "unavailable");
+        assert_eq!(json["message"], "Service unavailable: busy");
+    }
+
+    #[tokio::test]
+    async fn test_payload_too_large_response() {
+        let (status, json) = body_json(ApiError::PayloadTooLarge("too many orders".to_string())).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(json["code"], "payload_too_large");
+        assert_eq!(json["message"], "Payload too large: too many orders");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_response() {
+        let existing_id = Uuid::new_v4();
+        let response = ApiError::Duplicate(existing_id).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["details"]["existing_id"], existing_id.to_string());
+    }
+
     #[test]
     fn test_internal_error_response() {
-        let response = ApiError::Internal.into_response();
+        let response = ApiError::internal("cause").into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    /// Log output written by the subscriber under test
+    #[derive(Clone, Default)]
+    struct Output(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_cause_is_logged_but_not_sent() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let response = tracing::info_span!("request", request_id = "req-7")
+            .in_scope(|| ApiError::internal("disk full writing snapshot").into_response());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({ "code": "internal", "message": "Internal server error", "details": null }));
+
+        let logged = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let line = logged.lines().find(|line| line.contains("ERROR")).unwrap_or_default();
+        assert!(line.contains("disk full writing snapshot"), "{logged}");
+        assert!(line.contains("request_id=\"req-7\""), "{logged}");
+    }
+
+    #[tokio::test]
+    async fn test_panic_response_hides_message_unless_exposed() {
+        let response = panic_response(Box::new("secret detail"), false);
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("secret"));
+
+        let response = panic_response(Box::new(String::from("secret detail")), true);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["details"]["panic"], "secret detail");
     }
 
     #[test]
@@ -111,7 +433,69 @@ mod tests {
         });
         
         let response = ApiError::Validation(errors).into_response();
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    async fn body_json(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_codes_distinguish_malformed_from_invalid() {
+        let (status, json) = body_json(ApiError::BadRequest("expected value".to_string())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "bad_request");
+
+        let mut errors = ValidationErrors::new();
+        errors.add("field", validator::ValidationError::new("length"));
+        let (status, json) = body_json(ApiError::Validation(errors)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["code"], "validation_failed");
+
+        let (status, json) = body_json(ApiError::NotFound { resource: "order" }).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["code"], "order_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_validation_details_formats() {
+        let errors = || {
+            let mut errors = ValidationErrors::new();
+            errors.add("items", ValidationError::new("items_empty"));
+            errors.add("customer", ValidationError::new("customer_empty"));
+            errors.add("customer", ValidationError::new("customer_too_long"));
+            ApiError::Validation(errors)
+        };
+
+        let (_, json) = body_json(errors()).await;
+        assert_eq!(json["details"], serde_json::json!({
+            "customer": ["customer name must not be empty", "customer name is too long"],
+            "items": ["at least one item required"],
+        }));
+
+        let (status, json) = FORMAT.scope(ErrorFormat::Flat, body_json(errors())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["code"], "validation_failed");
+        assert_eq!(json["details"], serde_json::json!([
+            { "field": "customer", "message": "customer name must not be empty" },
+            { "field": "customer", "message": "customer name is too long" },
+            { "field": "items", "message": "at least one item required" },
+        ]));
+    }
+
+    #[tokio::test]
+    async fn test_validation_message_params_are_filled_in() {
+        let mut error = ValidationError::new("item_too_long");
+        error.add_param("index".into(), &2);
+        error.add_param("max".into(), &256);
+        let mut errors = ValidationErrors::new();
+        errors.add("items", error);
+
+        let (_, json) = body_json(ApiError::Validation(errors)).await;
+        assert_eq!(json["details"]["items"][0], "item 2 is longer than 256 characters");
     }
 
     #[test]
@@ -0,0 +1,288 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::{BTreeSet, HashMap}, fmt, sync::Arc};
+use uuid::Uuid;
+
+use crate::{models::Order, order_dtos::OrderResponseDto};
+
+/// Something that happened to an order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum OrderEvent {
+    OrderCreated { order_id: Uuid, customer: String, items: Vec<String> },
+    StatusChanged { order_id: Uuid, from: String, to: String, by: Option<String> },
+    /// The fields besides status after an assign, tag, freeze or patch
+    OrderUpdated {
+        order_id: Uuid,
+        customer: String,
+        items: Vec<String>,
+        assignee: Option<String>,
+        tags: BTreeSet<String>,
+        frozen: bool,
+    },
+    OrderDeleted { order_id: Uuid },
+    /// An order put back whole, by undoing a delete or restoring a backup
+    OrderRestored { order_id: Uuid, order: Box<Order> },
+}
+
+impl OrderEvent {
+    pub fn created(order: &Order) -> Self {
+        Self::OrderCreated {
+            order_id: order.id,
+            customer: order.customer.clone(),
+            items: order.items.iter().map(|item| item.to_string()).collect(),
+        }
+    }
+
+    pub fn updated(order: &Order) -> Self {
+        Self::OrderUpdated {
+            order_id: order.id,
+            customer: order.customer.clone(),
+            items: order.items.iter().map(|item| item.to_string()).collect(),
+            assignee: order.assignee.clone(),
+            tags: order.tags.clone(),
+            frozen: order.frozen,
+        }
+    }
+
+    pub fn restored(order: &Order) -> Self {
+        Self::OrderRestored { order_id: order.id, order: Box::new(order.clone()) }
+    }
+
+    /// The order's most recent status change, if it has one
+    pub fn last_status_change(order: &Order) -> Option<Self> {
+        order.history.last().map(|change| Self::StatusChanged {
+            order_id: order.id,
+            from: change.from.clone(),
+            to: change.to.clone(),
+            by: change.by.clone(),
+        })
+    }
+}
+
+/// An event with its place in the log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventRecord {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: OrderEvent,
+}
+
+/// Every order as of an event sequence number; applying later events to it gives the current state
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSnapshot {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub orders: Vec<OrderResponseDto>,
+}
+
+/// Where recorded events end up; in memory for now, a durable sink can implement this later
+pub trait EventSink: Send + Sync + fmt::Debug {
+    fn append(&mut self, record: EventRecord);
+    /// Records with a sequence number above `seq`, oldest first
+    fn since(&self, seq: u64) -> Vec<EventRecord>;
+    /// Drops the records numbered `seq` and below
+    fn trim_through(&mut self, seq: u64);
+}
+
+#[derive(Debug, Default)]
+pub struct MemorySink(Vec<EventRecord>);
+
+impl MemorySink {
+    /// Position of the first record above `seq`; sequence numbers are dense, so this is arithmetic
+    fn offset_after(&self, seq: u64) -> usize {
+        let first = self.0.first().map_or(1, |record| record.seq);
+        usize::try_from(seq.saturating_add(1).saturating_sub(first)).unwrap_or(usize::MAX).min(self.0.len())
+    }
+}
+
+impl EventSink for MemorySink {
+    fn append(&mut self, record: EventRecord) {
+        self.0.push(record);
+    }
+
+    fn since(&self, seq: u64) -> Vec<EventRecord> {
+        self.0[self.offset_after(seq)..].to_vec()
+    }
+
+    fn trim_through(&mut self, seq: u64) {
+        let end = self.offset_after(seq);
+        self.0.drain(..end);
+    }
+}
+
+/// Append-only log handing out sequence numbers, compacted behind periodic snapshots
+#[derive(Debug)]
+pub struct EventLog {
+    last_seq: u64,
+    /// Events up to this sequence number have been trimmed
+    trimmed_through: u64,
+    snapshot: Option<EventSnapshot>,
+    sink: Box<dyn EventSink>,
+}
+
+impl EventLog {
+    pub fn new(sink: Box<dyn EventSink>) -> Self {
+        Self { last_seq: 0, trimmed_through: 0, snapshot: None, sink }
+    }
+
+    pub fn record(&mut self, event: OrderEvent, at: DateTime<Utc>) {
+        self.last_seq += 1;
+        self.sink.append(EventRecord { seq: self.last_seq, at, event });
+    }
+
+    /// Events above `seq`, or the sequence number trimmed through if some of them are gone
+    pub fn since(&self, seq: u64) -> Result<Vec<EventRecord>, u64> {
+        if seq < self.trimmed_through {
+            return Err(self.trimmed_through);
+        }
+        Ok(self.sink.since(seq))
+    }
+
+    /// Snapshots `orders` as of the latest event and trims all but the `retain` most recent events,
+    /// so readers a little behind can still catch up without the snapshot
+    pub fn compact(&mut self, orders: Vec<OrderResponseDto>, at: DateTime<Utc>, retain: usize) {
+        self.snapshot = Some(EventSnapshot { seq: self.last_seq, at, orders });
+        let through = self.last_seq.saturating_sub(u64::try_from(retain).unwrap_or(u64::MAX));
+        if through > self.trimmed_through {
+            self.sink.trim_through(through);
+            self.trimmed_through = through;
+        }
+    }
+
+    pub fn snapshot(&self) -> Option<&EventSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Orders as the log describes them: the latest snapshot (or nothing) with every later event applied.
+    /// Orders taken from a snapshot start with an empty history.
+    pub fn replay(&self) -> HashMap<Uuid, Order> {
+        let (mut orders, seq) = match &self.snapshot {
+            Some(snapshot) => (snapshot.orders.iter().map(|dto| (dto.id, from_snapshot(dto))).collect(), snapshot.seq),
+            None => (HashMap::new(), 0),
+        };
+        for record in self.sink.since(seq) {
+            match record.event {
+                OrderEvent::OrderCreated { order_id, customer, items } => {
+                    let mut order = Order::new(customer, items, record.at);
+                    order.id = order_id;
+                    orders.insert(order_id, order);
+                }
+                OrderEvent::StatusChanged { order_id, to, by, .. } => {
+                    if let Some(order) = orders.get_mut(&order_id) {
+                        order.set_status(to, by, record.at);
+                    }
+                }
+                OrderEvent::OrderUpdated { order_id, customer, items, assignee, tags, frozen } => {
+                    if let Some(order) = orders.get_mut(&order_id) {
+                        order.customer = customer;
+                        order.items = items.into_iter().map(Arc::from).collect();
+                        order.assignee = assignee;
+                        order.tags = tags;
+                        order.frozen = frozen;
+                        order.updated_at = record.at;
+                    }
+                }
+                OrderEvent::OrderDeleted { order_id } => {
+                    orders.remove(&order_id);
+                }
+                OrderEvent::OrderRestored { order_id, order } => {
+                    orders.insert(order_id, *order);
+                }
+            }
+        }
+        orders
+    }
+}
+
+fn from_snapshot(dto: &OrderResponseDto) -> Order {
+    Order {
+        id: dto.id,
+        customer: dto.customer.clone(),
+        items: dto.items.iter().map(|item| Arc::from(item.as_str())).collect(),
+        status: dto.status.clone(),
+        assignee: dto.assignee.clone(),
+        tags: dto.tags.clone(),
+        frozen: dto.frozen,
+        created_at: dto.created_at,
+        updated_at: dto.updated_at,
+        history: Vec::new(),
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(Box::new(MemorySink::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_since_skips_seen_events() {
+        let mut log = EventLog::default();
+        let now = Utc::now();
+        for _ in 0..3 {
+            log.record(OrderEvent::OrderDeleted { order_id: Uuid::new_v4() }, now);
+        }
+
+        assert_eq!(log.since(0).unwrap().iter().map(|r| r.seq).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(log.since(2).unwrap().iter().map(|r| r.seq).collect::<Vec<_>>(), [3]);
+        assert!(log.since(3).unwrap().is_empty());
+        assert!(log.since(u64::MAX).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compact_keeps_retained_events() {
+        let mut log = EventLog::default();
+        let now = Utc::now();
+        for _ in 0..10 {
+            log.record(OrderEvent::OrderDeleted { order_id: Uuid::new_v4() }, now);
+        }
+        log.compact(Vec::new(), now, 3);
+
+        assert_eq!(log.snapshot().unwrap().seq, 10);
+        assert_eq!(log.since(6), Err(7));
+        assert_eq!(log.since(7).unwrap().iter().map(|r| r.seq).collect::<Vec<_>>(), [8, 9, 10]);
+
+        // Numbering carries on after a trim
+        log.record(OrderEvent::OrderDeleted { order_id: Uuid::new_v4() }, now);
+        assert_eq!(log.since(9).unwrap().iter().map(|r| r.seq).collect::<Vec<_>>(), [10, 11]);
+        log.compact(Vec::new(), now, 0);
+        assert!(log.since(11).unwrap().is_empty());
+        assert_eq!(log.since(10), Err(11));
+    }
+
+    #[test]
+    fn test_replay_applies_events_after_the_snapshot() {
+        let mut log = EventLog::default();
+        let now = Utc::now();
+        let order = Order::new("Alice".into(), vec!["Book".into()], now);
+        log.record(OrderEvent::created(&order), now);
+        log.compact(vec![order.clone().into()], now, 0);
+        log.record(OrderEvent::StatusChanged { order_id: order.id, from: "pending".into(), to: "shipped".into(), by: None }, now);
+        let gone = Order::new("Bob".into(), vec!["Pen".into()], now);
+        log.record(OrderEvent::created(&gone), now);
+        log.record(OrderEvent::OrderDeleted { order_id: gone.id }, now);
+
+        let orders = log.replay();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[&order.id].status, "shipped");
+        assert_eq!(orders[&order.id].history.len(), 1);
+    }
+
+    #[test]
+    fn test_record_serializes_with_type_tag() {
+        let mut log = EventLog::default();
+        let id = Uuid::new_v4();
+        log.record(OrderEvent::OrderDeleted { order_id: id }, Utc::now());
+
+        let json = serde_json::to_value(&log.since(0).unwrap()[0]).unwrap();
+        assert_eq!(json["seq"], 1);
+        assert_eq!(json["type"], "OrderDeleted");
+        assert_eq!(json["order_id"], id.to_string());
+    }
+}
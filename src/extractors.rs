@@ -0,0 +1,208 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Path, Query, Request},
+    http::{header, request::Parts},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::errors::ApiError;
+
+/// Query string extractor that runs `validate()` on the deserialized value
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        value.validate()?; // validation
+        Ok(ValidatedQuery(value))
+    }
+}
+
+/// How JSON bodies treat fields the target type does not have; set for the whole app as an extension
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonMode {
+    /// Reject unknown fields with 400 instead of ignoring them
+    pub strict: bool,
+}
+
+impl JsonMode {
+    /// In strict mode, fails with 400 naming the first field of `bytes` that `T` would ignore
+    pub fn check<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<(), ApiError> {
+        if !self.strict {
+            return Ok(());
+        }
+        let mut unknown = None;
+        let _: Result<T, _> = serde_ignored::deserialize(&mut serde_json::Deserializer::from_slice(bytes), |path| {
+            unknown.get_or_insert_with(|| path.to_string());
+        });
+        match unknown {
+            Some(field) => Err(ApiError::BadRequest(format!("unknown field `{field}`"))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `Json` body that, under a strict `JsonMode`, rejects fields `T` does not know, naming the first one
+pub struct JsonBody<T>(pub T);
+
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let mode = req.extensions().get::<JsonMode>().copied().unwrap_or_default();
+        if !mode.strict {
+            let Json(value) = Json::<T>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+            return Ok(JsonBody(value));
+        }
+        // Rebuilt around the buffered body so content type and syntax errors are reported exactly as `Json` does
+        let content_type = req.headers().get(header::CONTENT_TYPE).cloned();
+        let bytes = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        let mut rebuilt = Request::new(Body::from(bytes.clone()));
+        if let Some(content_type) = content_type {
+            rebuilt.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        let Json(value) = Json::<T>::from_request(rebuilt, state).await.map_err(IntoResponse::into_response)?;
+        mode.check::<T>(&bytes).map_err(IntoResponse::into_response)?;
+        Ok(JsonBody(value))
+    }
+}
+
+/// Optional body: no `Content-Type` means no body, as with `Option<Json<T>>`
+impl<T, S> axum::extract::OptionalFromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if !req.headers().contains_key(header::CONTENT_TYPE) {
+            return Ok(None);
+        }
+        <Self as FromRequest<S>>::from_request(req, state).await.map(Some)
+    }
+}
+
+/// `{id}` path segment parsed as an order id, rejected in the standard error body
+pub struct OrderId(pub Uuid);
+
+impl<S> FromRequestParts<S> for OrderId
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(id) = Path::<Uuid>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::BadRequest("invalid order id".into()))?;
+        Ok(OrderId(id))
+    }
+}
+
+/// Whether the request sent `Prefer: return=minimal` (RFC 7240), asking for no response body
+pub struct PreferMinimal(pub bool);
+
+impl<S> FromRequestParts<S> for PreferMinimal
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let minimal = parts
+            .headers
+            .get_all("prefer")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|preference| preference.trim().eq_ignore_ascii_case("return=minimal"));
+        Ok(PreferMinimal(minimal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_dtos::ListQuery;
+    use axum::{body::Body, http::{Request, StatusCode}, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn handler(ValidatedQuery(query): ValidatedQuery<ListQuery>) -> String {
+        format!("{:?} {:?}", query.status, query.limit)
+    }
+
+    async fn send(uri: &str) -> (StatusCode, String) {
+        let app = Router::new().route("/", get(handler));
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_valid_query() {
+        let (status, body) = send("/?status=pending&sort=-customer&limit=5&offset=10").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Some(\"pending\") Some(5)");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_query_values() {
+        let (status, body) = send("/?status=lost&limit=0").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["message"], "Validation failed");
+        assert_eq!(json["details"]["status"][0], "invalid status");
+        assert_eq!(json["details"]["limit"][0], "limit must be between 1 and 100");
+    }
+
+    #[tokio::test]
+    async fn test_order_id() {
+        async fn id_handler(OrderId(id): OrderId) -> String {
+            id.to_string()
+        }
+        let app = Router::new().route("/{id}", get(id_handler));
+        let id = Uuid::new_v4();
+
+        let request = Request::builder().uri(format!("/{id}")).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder().uri("/not-a-uuid").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "bad_request");
+        assert_eq!(json["message"], "Invalid input: invalid order id");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_query() {
+        let (status, body) = send("/?limit=ten").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(json["message"].as_str().unwrap().starts_with("Invalid input"));
+    }
+}
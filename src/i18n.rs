@@ -0,0 +1,117 @@
+use axum::{extract::Request, http::header, middleware::Next, response::Response};
+
+/// Languages validation messages are available in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Es,
+}
+
+impl Lang {
+    fn from_tag(tag: &str) -> Option<Lang> {
+        let primary = tag.split('-').next().unwrap_or_default();
+        match primary.to_ascii_lowercase().as_str() {
+            "en" => Some(Lang::En),
+            "es" => Some(Lang::Es),
+            _ => None,
+        }
+    }
+
+    /// Picks the supported language with the highest q-value, defaulting to English
+    pub fn from_accept_language(value: &str) -> Lang {
+        let mut candidates: Vec<(Lang, f32)> = value
+            .split(',')
+            .filter_map(|part| {
+                let mut pieces = part.trim().split(';');
+                let lang = Lang::from_tag(pieces.next()?.trim())?;
+                let q = pieces
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                Some((lang, q))
+            })
+            .collect();
+        // Stable sort keeps header order for equal weights
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.first().map(|(lang, _)| *lang).unwrap_or(Lang::En)
+    }
+}
+
+tokio::task_local! {
+    static LANG: Lang;
+}
+
+/// Language negotiated for the request currently being handled
+pub fn current() -> Lang {
+    LANG.try_with(|lang| *lang).unwrap_or(Lang::En)
+}
+
+/// Runs `f` with `lang` as the current language, for work that outlives the middleware such as a streamed body
+pub async fn with_lang<F: Future>(lang: Lang, f: F) -> F::Output {
+    LANG.scope(lang, f).await
+}
+
+/// Localized message for a validation error code: (code, english, spanish)
+const MESSAGES: &[(&str, &str, &str)] = &[
+    ("customer_empty", "customer name must not be empty", "el nombre del cliente no puede estar vacío"),
+    ("customer_too_long", "customer name is too long", "el nombre del cliente es demasiado largo"),
+    ("items_empty", "at least one item required", "se requiere al menos un artículo"),
+    ("item_too_long", "item {index} is longer than {max} characters", "el artículo {index} supera los {max} caracteres"),
+    ("invalid_status", "invalid status", "estado no válido"),
+    ("assignee_empty", "assignee must not be empty", "el responsable no puede estar vacío"),
+    ("by_empty", "by must not be empty", "by no puede estar vacío"),
+    ("invalid_sort", "invalid sort field", "campo de ordenación no válido"),
+    ("limit_range", "limit must be between 1 and 100", "el límite debe estar entre 1 y 100"),
+    ("tag_empty", "tags must not be empty", "las etiquetas no pueden estar vacías"),
+    ("query_empty", "search query must not be empty", "la búsqueda no puede estar vacía"),
+];
+
+pub fn translate(code: &str, lang: Lang) -> Option<&'static str> {
+    MESSAGES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, en, es)| match lang {
+            Lang::En => *en,
+            Lang::Es => *es,
+        })
+}
+
+/// Middleware making the `Accept-Language` choice available while the request is handled
+pub async fn negotiate_language(request: Request, next: Next) -> Response {
+    let lang = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(Lang::from_accept_language)
+        .unwrap_or(Lang::En);
+    LANG.scope(lang, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_language_parsing() {
+        assert_eq!(Lang::from_accept_language("es"), Lang::Es);
+        assert_eq!(Lang::from_accept_language("es-MX,en;q=0.5"), Lang::Es);
+        assert_eq!(Lang::from_accept_language("en;q=0.9,es;q=0.4"), Lang::En);
+        assert_eq!(Lang::from_accept_language("fr,es;q=0.8"), Lang::Es);
+        assert_eq!(Lang::from_accept_language("fr-FR"), Lang::En);
+        assert_eq!(Lang::from_accept_language(""), Lang::En);
+    }
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(translate("invalid_status", Lang::En), Some("invalid status"));
+        assert_eq!(translate("invalid_status", Lang::Es), Some("estado no válido"));
+        assert_eq!(translate("unknown_code", Lang::Es), None);
+    }
+
+    #[tokio::test]
+    async fn test_current_defaults_to_english() {
+        assert_eq!(current(), Lang::En);
+        let lang = LANG.scope(Lang::Es, async { current() }).await;
+        assert_eq!(lang, Lang::Es);
+    }
+}
@@ -0,0 +1,21 @@
+//! Order management API: the axum app plus, behind the `client` feature, a typed client for it
+
+pub mod cache;
+pub mod clock;
+pub mod config;
+pub mod models;
+pub mod order_dtos;
+pub mod errors;
+pub mod events;
+pub mod db;
+pub mod extractors;
+pub mod i18n;
+pub mod listener;
+pub mod logging;
+pub mod pool;
+pub mod routes;
+pub mod timeouts;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "client")]
+pub mod client;
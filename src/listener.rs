@@ -0,0 +1,150 @@
+use axum::serve::Listener;
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tracing::warn;
+
+/// Hands out one permit per open connection, up to `max`
+#[derive(Debug, Clone)]
+pub struct ConnectionLimiter {
+    permits: Arc<Semaphore>,
+    max: usize,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// None once `max` connections hold a permit
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.permits.clone().try_acquire_owned().ok()
+    }
+
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+}
+
+/// Listener that closes connections straight after accepting them once the limit is reached
+pub struct LimitedListener<L> {
+    inner: L,
+    limiter: ConnectionLimiter,
+}
+
+impl<L> LimitedListener<L> {
+    pub fn new(inner: L, max_connections: usize) -> Self {
+        Self {
+            inner,
+            limiter: ConnectionLimiter::new(max_connections),
+        }
+    }
+}
+
+impl<L> Listener for LimitedListener<L>
+where
+    L: Listener,
+    L::Addr: std::fmt::Debug,
+{
+    type Io = LimitedIo<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            let (io, addr) = self.inner.accept().await;
+            match self.limiter.try_acquire() {
+                Some(permit) => return (LimitedIo { inner: io, _permit: permit }, addr),
+                // Dropping the io closes the connection
+                None => warn!("Connection limit of {} reached, rejecting {:?}", self.limiter.max, addr),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+/// Connection that gives its permit back when closed
+pub struct LimitedIo<T> {
+    inner: T,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for LimitedIo<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for LimitedIo<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::{io::AsyncReadExt, net::{TcpListener, TcpStream}};
+
+    #[test]
+    fn test_limiter_enforces_cap() {
+        let limiter = ConnectionLimiter::new(2);
+        let first = limiter.try_acquire();
+        let second = limiter.try_acquire();
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(limiter.try_acquire().is_none());
+        assert_eq!(limiter.available(), 0);
+
+        // Closing a connection frees its slot
+        drop(first);
+        assert_eq!(limiter.available(), 1);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_listener_rejects_excess_connections() {
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener = LimitedListener::new(tcp, 1);
+
+        let accepted = tokio::spawn(async move {
+            let (io, _) = listener.accept().await;
+            // Keep accepting so the second connection is seen (and closed)
+            let second = tokio::time::timeout(Duration::from_millis(200), listener.accept()).await;
+            (io, second.is_ok())
+        });
+
+        let _first = TcpStream::connect(addr).await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+
+        // The excess connection is closed by the server without any data
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(1), second.read(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))));
+
+        let (_io, second_accepted) = accepted.await.unwrap();
+        assert!(!second_accepted);
+    }
+}
@@ -0,0 +1,176 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    time::{Duration, Instant},
+};
+
+/// Every finished request, logged or not, for the `/metrics` counter
+static REQUESTS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+pub fn requests_total() -> u64 {
+    REQUESTS_TOTAL.load(Ordering::Relaxed)
+}
+
+/// Decides which finished requests are logged: one in `every`, plus all errors and slow requests
+#[derive(Debug)]
+pub struct LogSampler {
+    /// 1 logs everything, 0 logs only errors and slow requests
+    every: u64,
+    slow: Duration,
+    seen: AtomicU64,
+}
+
+impl LogSampler {
+    pub fn new(every: u64, slow: Duration) -> Self {
+        Self {
+            every,
+            slow,
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts the request and says whether it should be logged
+    pub fn should_log(&self, status: StatusCode, latency: Duration) -> bool {
+        REQUESTS_TOTAL.fetch_add(1, Ordering::Relaxed);
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        status.is_client_error()
+            || status.is_server_error()
+            || latency >= self.slow
+            || (self.every > 0 && n.is_multiple_of(self.every))
+    }
+}
+
+/// How long each route may take before a warning is logged, keyed by matched path (e.g. `/orders/{id}`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyBudgets(HashMap<String, Duration>);
+
+impl LatencyBudgets {
+    /// Parses `route=millis` pairs separated by commas; malformed pairs are skipped
+    pub fn parse(spec: &str) -> Self {
+        Self(parse_route_millis(spec))
+    }
+
+    /// The route's budget, if it has one and `latency` went over it
+    pub fn exceeded(&self, route: &str, latency: Duration) -> Option<Duration> {
+        self.0.get(route).copied().filter(|budget| latency > *budget)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// `route=millis` pairs separated by commas, keyed by matched path; malformed pairs are skipped
+pub(crate) fn parse_route_millis(spec: &str) -> HashMap<String, Duration> {
+    spec.split(',')
+        .filter_map(|pair| pair.trim().rsplit_once('='))
+        .filter_map(|(route, ms)| Some((route.trim().to_string(), Duration::from_millis(ms.trim().parse().ok()?))))
+        .collect()
+}
+
+/// Warns when a request takes longer than its route's budget; runs inside the request span for the id
+pub async fn enforce_budgets(State(budgets): State<Arc<LatencyBudgets>>, request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let latency = started.elapsed();
+    if let Some(route) = route
+        && let Some(budget) = budgets.exceeded(&route, latency)
+    {
+        tracing::warn!(route, ?budget, ?latency, "Request over its latency budget");
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(1);
+    const SLOW: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_rate_zero_logs_only_errors_and_slow_requests() {
+        let sampler = LogSampler::new(0, SLOW);
+        let before = requests_total();
+
+        assert!(!sampler.should_log(StatusCode::OK, FAST));
+        assert!(!sampler.should_log(StatusCode::CREATED, FAST));
+        assert!(sampler.should_log(StatusCode::NOT_FOUND, FAST));
+        assert!(sampler.should_log(StatusCode::INTERNAL_SERVER_ERROR, FAST));
+        assert!(sampler.should_log(StatusCode::OK, SLOW));
+
+        // Sampled out requests are still counted
+        assert!(requests_total() >= before + 5);
+    }
+
+    #[test]
+    fn test_parse_budgets() {
+        let budgets = LatencyBudgets::parse("/orders=200, /orders/{id}=50,garbage,/metrics=soon");
+        assert_eq!(budgets.exceeded("/orders", Duration::from_millis(201)), Some(Duration::from_millis(200)));
+        assert_eq!(budgets.exceeded("/orders", Duration::from_millis(200)), None);
+        assert_eq!(budgets.exceeded("/orders/{id}", Duration::from_millis(60)), Some(Duration::from_millis(50)));
+        assert_eq!(budgets.exceeded("/metrics", Duration::from_secs(9)), None);
+        assert!(LatencyBudgets::parse("").is_empty());
+    }
+
+    /// Log output written by the subscriber under test
+    #[derive(Clone, Default)]
+    struct Output(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_warns_with_route_and_request_id() {
+        use axum::{body::Body, middleware, routing::get, Router};
+        use tower::ServiceExt;
+        use tracing::Instrument;
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let budgets = Arc::new(LatencyBudgets::parse("/slow/{n}=5,/fast=1000"));
+        let app = Router::new()
+            .route("/slow/{n}", get(|| async { tokio::time::sleep(Duration::from_millis(20)).await }))
+            .route("/fast", get(|| async {}))
+            .layer(middleware::from_fn_with_state(budgets, enforce_budgets));
+        for uri in ["/slow/1", "/fast"] {
+            let request = axum::http::Request::get(uri).body(Body::empty()).unwrap();
+            let span = tracing::info_span!("request", request_id = "req-42");
+            app.clone().oneshot(request).instrument(span).await.unwrap();
+        }
+
+        let logged = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let warnings: Vec<&str> = logged.lines().filter(|line| line.contains("WARN")).collect();
+        assert_eq!(warnings.len(), 1, "{logged}");
+        assert!(warnings[0].contains("request_id=\"req-42\""), "{logged}");
+        assert!(warnings[0].contains("route=\"/slow/{n}\""), "{logged}");
+    }
+
+    #[test]
+    fn test_samples_one_in_n() {
+        let sampler = LogSampler::new(3, SLOW);
+        let logged: Vec<bool> = (0..6).map(|_| sampler.should_log(StatusCode::OK, FAST)).collect();
+        assert_eq!(logged, [true, false, false, true, false, false]);
+
+        let everything = LogSampler::new(1, SLOW);
+        assert!((0..4).all(|_| everything.should_log(StatusCode::OK, FAST)));
+    }
+}
@@ -1,35 +1,35 @@
-pub mod models;
-pub mod order_dtos;
-pub mod errors;
-pub mod db;
-pub mod routes;
-
-
-use crate::db::Db;
-use std::{collections::HashMap, env, net::SocketAddr, sync::Arc};
+use rust_assignment::{clock::SystemClock, config::Config, db::{self, Db, Store}, listener::LimitedListener, routes};
+use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() {
-    let db: Db = Arc::new(RwLock::new(HashMap::new()));
-    let app = routes::app(db);
-
     // Load environment variables from .env file
     dotenv::dotenv().ok();
+    let config = Config::from_env();
+    config.check().expect("Invalid configuration");
+    db::set_slow_lock_threshold(config.slow_lock_threshold);
 
-    if env::var("DEV_LOGGING").unwrap_or_else(|_| "0".into()) == "1" {
+    let db: Db = Arc::new(RwLock::new(Store::new(&config, Box::new(SystemClock))));
+    if let Some(interval) = config.event_compaction_interval {
+        db::spawn_event_compaction(db.clone(), interval, config.event_retention);
+    }
+    let app = routes::app(db, &config);
+
+    if config.dev_logging {
         tracing_subscriber::fmt()
             .with_env_filter("tower_http=trace,info")
             .init();
     }
     
-    let host = std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr: SocketAddr = format!("{}:{}", host, port).parse().expect("Invalid host/port");
+    let addr: SocketAddr = format!("{}:{}", config.host, config.port).parse().expect("Invalid host/port");
     println!("Server running at http://{}", addr);
 
     // New Axum 0.7 style
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    match config.max_connections {
+        Some(max) => axum::serve(LimitedListener::new(listener, max), app).await.unwrap(),
+        None => axum::serve(listener, app).await.unwrap(),
+    }
 }
 
@@ -1,13 +1,95 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc};
 use uuid::Uuid;
 
 /// Domain model (not exposed directly in requests)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Order {
     pub id: Uuid,
     pub customer: String,
-    pub items: Vec<String>,
+    /// Shared with every other order naming the same item once stored
+    pub items: Vec<Arc<str>>,
     pub status: String,
+    /// Staff member following up on the order
+    pub assignee: Option<String>,
+    /// Free-form labels such as a promo name, kept sorted and unique
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Held for review: no status change is allowed until unfrozen
+    #[serde(default)]
+    pub frozen: bool,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Last time any field changed
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub history: Vec<StatusChange>,
+}
+
+/// A recorded move from one status to another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub from: String,
+    pub to: String,
+    pub at: DateTime<Utc>,
+    /// Who made the change, when known
+    pub by: Option<String>,
+}
+
+/// Every status an order can be in
+pub const STATUSES: &[&str] = &["pending", "shipped", "delivered", "cancelled"];
+
+/// Statuses an order may move to from the given one
+pub fn allowed_transitions(status: &str) -> &'static [&'static str] {
+    match status {
+        "pending" => &["shipped", "cancelled"],
+        "shipped" => &["delivered"],
+        _ => &[],
+    }
+}
+
+pub fn can_transition(from: &str, to: &str) -> bool {
+    allowed_transitions(from).contains(&to)
+}
+
+impl Order {
+    /// New pending order with a fresh id, created at `now`
+    pub fn new(customer: String, items: Vec<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            customer,
+            items: items.into_iter().map(Arc::from).collect(),
+            status: "pending".into(),
+            assignee: None,
+            tags: BTreeSet::new(),
+            frozen: false,
+            created_at: now,
+            updated_at: now,
+            history: Vec::new(),
+        }
+    }
+
+    /// Status at a recorded version: 0 as created, n after the n-th status change
+    pub fn status_at(&self, version: usize) -> Option<&str> {
+        match version {
+            0 => Some(self.history.first().map_or(self.status.as_str(), |c| c.from.as_str())),
+            n => self.history.get(n - 1).map(|c| c.to.as_str()),
+        }
+    }
+
+    /// Changes the status at `at` and records the change in the history
+    pub fn set_status(&mut self, status: String, by: Option<String>, at: DateTime<Utc>) {
+        let from = std::mem::replace(&mut self.status, status);
+        self.updated_at = at;
+        self.history.push(StatusChange {
+            from,
+            to: self.status.clone(),
+            at: self.updated_at,
+            by,
+        });
+    }
 }
 
 #[cfg(test)]
@@ -16,28 +98,25 @@ mod tests {
 
     #[test]
     fn test_order_creation() {
-        let id = Uuid::new_v4();
-        let order = Order {
-            id,
-            customer: "John Doe".to_string(),
-            items: vec!["Item 1".to_string(), "Item 2".to_string()],
-            status: "pending".to_string(),
-        };
+        let order = Order::new(
+            "John Doe".to_string(),
+            vec!["Item 1".to_string(), "Item 2".to_string()], Utc::now(),
+        );
 
         assert_eq!(order.customer, "John Doe");
         assert_eq!(order.items.len(), 2);
         assert_eq!(order.status, "pending");
+        assert!(order.assignee.is_none());
+        assert!(order.tags.is_empty());
+        assert!(!order.frozen);
+        assert!(order.history.is_empty());
     }
 
     #[test]
     fn test_order_serialization() {
-        let id = Uuid::new_v4();
-        let order = Order {
-            id,
-            customer: "Jane Smith".to_string(),
-            items: vec!["Product A".to_string()],
-            status: "shipped".to_string(),
-        };
+        let mut order = Order::new("Jane Smith".to_string(), vec!["Product A".to_string()], Utc::now());
+        order.set_status("shipped".to_string(), None, Utc::now());
+        order.assignee = Some("alice".to_string());
 
         let json = serde_json::to_string(&order).unwrap();
         let deserialized: Order = serde_json::from_str(&json).unwrap();
@@ -46,5 +125,60 @@ mod tests {
         assert_eq!(order.customer, deserialized.customer);
         assert_eq!(order.items, deserialized.items);
         assert_eq!(order.status, deserialized.status);
+        assert_eq!(order.assignee, deserialized.assignee);
+        assert_eq!(order.created_at, deserialized.created_at);
+        assert_eq!(order.history, deserialized.history);
+    }
+
+    #[test]
+    fn test_order_deserialization_without_history() {
+        let json = r#"{"id":"67e55044-10b1-426f-9247-bb680e5fe0c8","customer":"Jane","items":["A"],"status":"pending"}"#;
+        let order: Order = serde_json::from_str(json).unwrap();
+        assert!(order.assignee.is_none());
+        assert!(order.history.is_empty());
+    }
+
+    #[test]
+    fn test_set_status_records_history() {
+        let mut order = Order::new("John Doe".to_string(), vec!["Item 1".to_string()], Utc::now());
+        order.set_status("shipped".to_string(), None, Utc::now());
+        order.set_status("delivered".to_string(), Some("courier".to_string()), Utc::now());
+
+        assert_eq!(order.status, "delivered");
+        assert_eq!(order.history.len(), 2);
+        assert_eq!(order.history[0].from, "pending");
+        assert_eq!(order.history[0].to, "shipped");
+        assert_eq!(order.history[1].from, "shipped");
+        assert_eq!(order.history[1].to, "delivered");
+        assert!(order.history[0].by.is_none());
+        assert_eq!(order.history[1].by.as_deref(), Some("courier"));
+        assert!(order.history[0].at <= order.history[1].at);
+        assert_eq!(order.updated_at, order.history[1].at);
+    }
+
+    #[test]
+    fn test_status_at() {
+        let mut order = Order::new("John Doe".to_string(), vec!["Item 1".to_string()], Utc::now());
+        assert_eq!(order.status_at(0), Some("pending"));
+        assert_eq!(order.status_at(1), None);
+
+        order.set_status("shipped".to_string(), None, Utc::now());
+        order.set_status("delivered".to_string(), None, Utc::now());
+        assert_eq!(order.status_at(0), Some("pending"));
+        assert_eq!(order.status_at(1), Some("shipped"));
+        assert_eq!(order.status_at(2), Some("delivered"));
+        assert_eq!(order.status_at(3), None);
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert_eq!(allowed_transitions("pending"), ["shipped", "cancelled"]);
+        assert_eq!(allowed_transitions("shipped"), ["delivered"]);
+        assert!(allowed_transitions("delivered").is_empty());
+        assert!(allowed_transitions("cancelled").is_empty());
+
+        assert!(can_transition("pending", "shipped"));
+        assert!(!can_transition("pending", "delivered"));
+        assert!(!can_transition("delivered", "pending"));
     }
 }
@@ -1,33 +1,516 @@
+use axum::{extract::{Request, State}, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate;
-use crate::models::Order;
+use validator::{Validate, ValidationError};
+use crate::models::{Order, StatusChange};
 
+/// Longest accepted names, in characters, as configured (`MAX_CUSTOMER_LEN`, `MAX_ITEM_LEN`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthLimits {
+    pub customer: usize,
+    pub item: usize,
+}
 
-/// Request DTO for creating an order
-#[derive(Debug, Deserialize, Validate)]
+impl Default for LengthLimits {
+    fn default() -> Self {
+        Self { customer: 200, item: 256 }
+    }
+}
+
+tokio::task_local! {
+    static LIMITS: LengthLimits;
+}
+
+/// Middleware making the configured length limits apply to DTOs validated while the request is handled
+pub async fn use_limits(State(limits): State<LengthLimits>, request: Request, next: Next) -> Response {
+    LIMITS.scope(limits, next.run(request)).await
+}
+
+/// Length limits for the request currently being handled
+pub fn current_limits() -> LengthLimits {
+    LIMITS.try_with(|limits| *limits).unwrap_or_default()
+}
+
+/// Runs `f` with `limits` as the current length limits, for DTOs validated after the middleware returned
+pub async fn with_limits<F: Future>(limits: LengthLimits, f: F) -> F::Output {
+    LIMITS.scope(limits, f).await
+}
+
+/// Request DTO for creating an order. There is deliberately no `status`: a supplied one is ignored and
+/// every order starts `pending`, so the lifecycle can't be skipped (`/orders/instant` is the explicit way)
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
 pub struct CreateOrderDto {
-    #[validate(length(min = 1, message = "customer name must not be empty"))]
+    #[validate(length(min = 1, code = "customer_empty", message = "customer name must not be empty"))]
+    #[validate(custom = "validate_customer_length")]
     pub customer: String,
 
-    #[validate(length(min = 1, message = "at least one item required"))]
+    #[validate(length(min = 1, code = "items_empty", message = "at least one item required"))]
+    #[validate(custom = "validate_item_lengths")]
     pub items: Vec<String>,
 }
 
-/// Request DTO for updating status
+/// Request DTO for creating an order that is already fulfilled. The create fields are spelled out
+/// rather than flattened from `CreateOrderDto`, so strict JSON mode can see unknown fields.
 #[derive(Debug, Deserialize, Validate)]
+pub struct InstantOrderDto {
+    #[validate(length(min = 1, code = "customer_empty", message = "customer name must not be empty"))]
+    #[validate(custom = "validate_customer_length")]
+    pub customer: String,
+
+    #[validate(length(min = 1, code = "items_empty", message = "at least one item required"))]
+    #[validate(custom = "validate_item_lengths")]
+    pub items: Vec<String>,
+
+    /// Defaults to `shipped`
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
+    pub status: Option<String>,
+}
+
+/// A saved create body for recurring orders, instantiated by name with `POST /templates/{name}/instantiate`
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct OrderTemplateDto {
+    #[validate(regex(path = "TEMPLATE_NAME_REGEX", code = "invalid_template_name", message = "template name must be 1 to 64 letters, digits, - or _"))]
+    pub name: String,
+
+    #[validate(length(min = 1, code = "customer_empty", message = "customer name must not be empty"))]
+    #[validate(custom = "validate_customer_length")]
+    pub customer: String,
+
+    #[validate(length(min = 1, code = "items_empty", message = "at least one item required"))]
+    #[validate(custom = "validate_item_lengths")]
+    pub items: Vec<String>,
+}
+
+impl OrderTemplateDto {
+    /// The create body this template stands for
+    pub fn order(&self) -> CreateOrderDto {
+        CreateOrderDto {
+            customer: self.customer.clone(),
+            items: self.items.clone(),
+        }
+    }
+}
+
+/// Request DTO for updating status
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
 pub struct UpdateStatusDto {
-    #[validate(regex(path = "STATUS_REGEX", message = "invalid status"))]
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
     pub status: String,
+
+    /// Compare-and-set: only apply the change if the order is currently in this status
+    #[serde(default)]
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
+    pub expected: Option<String>,
+}
+
+impl UpdateStatusDto {
+    /// Lowercases both statuses ahead of validation when `case` is lenient
+    pub fn with_case(mut self, case: StatusCase) -> Self {
+        if case == StatusCase::Lenient {
+            self.status = self.status.to_lowercase();
+            self.expected = self.expected.map(|expected| expected.to_lowercase());
+        }
+        self
+    }
+}
+
+/// Whether a status change must name the status in lower case
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum StatusCase {
+    /// `PENDING` is rejected as an invalid status
+    #[default]
+    Strict,
+    /// `PENDING` is accepted and stored as `pending`
+    Lenient,
+}
+
+impl StatusCase {
+    /// `strict` or `lenient`; anything else is None
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "strict" => Some(Self::Strict),
+            "lenient" => Some(Self::Lenient),
+            _ => None,
+        }
+    }
+}
+
+/// Request DTO for confirming delivery (the body is optional)
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct DeliveredDto {
+    #[validate(length(min = 1, code = "by_empty", message = "by must not be empty"))]
+    pub by: Option<String>,
+}
+
+/// Request DTO for setting (or clearing, with `null`) the assignee
+#[derive(Debug, Deserialize, Validate)]
+pub struct AssigneeDto {
+    #[validate(length(min = 1, code = "assignee_empty", message = "assignee must not be empty"))]
+    pub assignee: Option<String>,
+}
+
+/// Editable fields of an order after a merge patch has been applied, re-validated as a whole
+#[derive(Debug, Deserialize, Validate)]
+pub struct OrderPatchDto {
+    #[validate(length(min = 1, code = "customer_empty", message = "customer name must not be empty"))]
+    #[validate(custom = "validate_customer_length")]
+    pub customer: String,
+
+    #[validate(length(min = 1, code = "items_empty", message = "at least one item required"))]
+    #[validate(custom = "validate_item_lengths")]
+    pub items: Vec<String>,
+
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
+    pub status: String,
+
+    #[validate(length(min = 1, code = "assignee_empty", message = "assignee must not be empty"))]
+    pub assignee: Option<String>,
+}
+
+/// One row of a CSV import; `items` holds pipe-separated item names
+#[derive(Debug, Deserialize)]
+pub struct CsvOrderRow {
+    pub customer: String,
+    pub items: String,
+}
+
+impl From<CsvOrderRow> for CreateOrderDto {
+    fn from(row: CsvOrderRow) -> Self {
+        Self {
+            customer: row.customer.trim().to_string(),
+            items: row
+                .items
+                .split('|')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// One row of a CSV export, in the import's column layout plus the order's state
+#[derive(Debug, Serialize)]
+pub struct CsvExportRow {
+    pub id: Uuid,
+    pub customer: String,
+    pub items: String,
+    pub status: String,
+    pub assignee: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CsvExportRow {
+    /// Column names in field order, for an export without any row to take them from
+    pub const HEADER: [&str; 6] = ["id", "customer", "items", "status", "assignee", "created_at"];
+}
+
+impl From<&Order> for CsvExportRow {
+    fn from(order: &Order) -> Self {
+        Self {
+            id: order.id,
+            customer: order.customer.clone(),
+            items: order.items.join("|"),
+            status: order.status.clone(),
+            assignee: order.assignee.clone(),
+            created_at: order.created_at,
+        }
+    }
+}
+
+impl From<&OrderResponseDto> for CsvExportRow {
+    fn from(order: &OrderResponseDto) -> Self {
+        Self {
+            id: order.id,
+            customer: order.customer.clone(),
+            items: order.items.join("|"),
+            status: order.status.clone(),
+            assignee: order.assignee.clone(),
+            created_at: order.created_at,
+        }
+    }
+}
+
+/// Outcome of a CSV import: the orders created plus why each other row was skipped
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummaryDto {
+    pub imported: Vec<Uuid>,
+    pub errors: Vec<ImportRowErrorDto>,
 }
 
-/// Response DTO
 #[derive(Debug, Serialize)]
+pub struct ImportRowErrorDto {
+    /// Line in the uploaded file, counting the header as line 1
+    pub line: u64,
+    pub error: String,
+}
+
+/// Filter for bulk operations; at least one field must be set
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderFilter {
+    pub status: Option<String>,
+    /// Orders created strictly before this instant
+    pub created_before: Option<DateTime<Utc>>,
+    /// Orders carrying this tag
+    pub tag: Option<String>,
+}
+
+impl OrderFilter {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none() && self.created_before.is_none() && self.tag.is_none()
+    }
+
+    pub fn matches(&self, order: &Order) -> bool {
+        self.status.as_ref().is_none_or(|s| &order.status == s)
+            && self.created_before.is_none_or(|before| order.created_at < before)
+            && self.tag.as_ref().is_none_or(|tag| order.tags.contains(tag))
+    }
+}
+
+/// Tag changes applied to every order matching `filter`
+#[derive(Debug, Deserialize, Validate)]
+pub struct BulkTagDto {
+    pub filter: OrderFilter,
+
+    #[serde(default)]
+    #[validate(custom = "validate_tags")]
+    pub add: Vec<String>,
+
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// One status change in a batch
+#[derive(Debug, Deserialize)]
+pub struct BulkStatusItemDto {
+    pub id: Uuid,
+    pub status: String,
+    /// Compare-and-set, as for a single change
+    #[serde(default)]
+    pub expected: Option<String>,
+}
+
+/// Status changes taken in order, so a later one sees the status an earlier one left behind
+#[derive(Debug, Deserialize)]
+pub struct BulkStatusDto {
+    pub updates: Vec<BulkStatusItemDto>,
+}
+
+impl BulkStatusDto {
+    /// Each change as the single-order update it stands for
+    pub fn as_updates(&self, case: StatusCase) -> Vec<(Uuid, UpdateStatusDto)> {
+        self.updates
+            .iter()
+            .map(|item| (item.id, UpdateStatusDto { status: item.status.clone(), expected: item.expected.clone() }.with_case(case)))
+            .collect()
+    }
+}
+
+/// Rejects a customer name longer than the current customer limit
+fn validate_customer_length(customer: &str) -> Result<(), ValidationError> {
+    let max = current_limits().customer;
+    if customer.chars().count() > max {
+        let mut error = ValidationError::new("customer_too_long");
+        error.message = Some("customer name is too long".into());
+        error.add_param("max".into(), &max);
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Rejects the first item longer than the current item limit, naming its index
+fn validate_item_lengths(items: &[String]) -> Result<(), ValidationError> {
+    let max = current_limits().item;
+    if let Some(index) = items.iter().position(|item| item.chars().count() > max) {
+        let mut error = ValidationError::new("item_too_long");
+        error.add_param("index".into(), &index);
+        error.add_param("max".into(), &max);
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.iter().any(|tag| tag.trim().is_empty()) {
+        return Err(ValidationError::new("tag_empty"));
+    }
+    Ok(())
+}
+
+/// Query parameters for listing orders
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct ListQuery {
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
+    pub status: Option<String>,
+
+    pub assignee: Option<String>,
+
+    /// Only orders containing this item (case-insensitive exact match)
+    pub item: Option<String>,
+
+    #[validate(regex(path = "SORT_REGEX", code = "invalid_sort", message = "invalid sort field"))]
+    pub sort: Option<String>,
+
+    #[validate(range(min = 1, max = 100, code = "limit_range", message = "limit must be between 1 and 100"))]
+    pub limit: Option<usize>,
+
+    pub offset: Option<usize>,
+
+    #[serde(flatten)]
+    pub created: CreatedRange,
+}
+
+/// Bounds on `created_at` for lists and exports: `created_after` is inclusive and `created_before`
+/// exclusive, so consecutive months cover every order exactly once
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, Validate)]
+pub struct CreatedRange {
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl CreatedRange {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.created_after.is_none_or(|after| at >= after) && self.created_before.is_none_or(|before| at < before)
+    }
+
+    /// Describes bounds that leave nothing in between, if they do
+    pub fn conflict(&self) -> Option<String> {
+        match (self.created_after, self.created_before) {
+            (Some(after), Some(before)) if before <= after => Some("created_before must be later than created_after".into()),
+            _ => None,
+        }
+    }
+}
+
+/// Order of list results before any `sort`, and between orders a `sort` ranks equal
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ListOrder {
+    /// Oldest first, ties broken by id
+    #[default]
+    CreatedAt,
+    Id,
+}
+
+impl ListOrder {
+    /// `created_at` or `id`; anything else is None
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "created_at" => Some(Self::CreatedAt),
+            "id" => Some(Self::Id),
+            _ => None,
+        }
+    }
+}
+
+/// How item names are rewritten on the way in
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ItemNormalization {
+    /// Stored exactly as sent
+    #[default]
+    None,
+    /// Trimmed and Title Cased; words matching one in `keep` (ignoring case) take its spelling instead
+    TitleCase { keep: Vec<String> },
+}
+
+impl ItemNormalization {
+    /// `titlecase` with a comma separated list of words to keep as spelled (e.g. `USB,HDMI`); anything else is None
+    pub fn parse(mode: &str, keep: &str) -> Option<Self> {
+        match mode.trim() {
+            "titlecase" => Some(Self::TitleCase {
+                keep: keep.split(',').map(str::trim).filter(|w| !w.is_empty()).map(String::from).collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, item: String) -> String {
+        let Self::TitleCase { keep } = self else { return item };
+        let words: Vec<String> = item
+            .trim()
+            .split(' ')
+            .map(|word| match keep.iter().find(|k| k.eq_ignore_ascii_case(word)) {
+                Some(kept) => kept.clone(),
+                None => capitalize(word),
+            })
+            .collect();
+        words.join(" ")
+    }
+
+    pub fn apply_all(&self, items: Vec<String>) -> Vec<String> {
+        items.into_iter().map(|item| self.apply(item)).collect()
+    }
+}
+
+/// First letter upper case, the rest lower case
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+impl ListQuery {
+    /// Describes a combination of parameters that cannot be served coherently, if there is one
+    pub fn conflict(&self) -> Option<String> {
+        let sort_field = self.sort.as_deref().map(|sort| sort.trim_start_matches('-'));
+        if sort_field == Some("status") && self.status.is_some() {
+            return Some("sort=status conflicts with the status filter: every result has the same status".into());
+        }
+        if self.offset.is_some() && self.limit.is_none() {
+            return Some("offset requires limit: without a page size there is no next page to skip to".into());
+        }
+        self.created.conflict()
+    }
+}
+
+/// Query parameters for full-text search
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchQuery {
+    #[validate(length(min = 1, code = "query_empty", message = "search query must not be empty"))]
+    pub q: String,
+
+    #[validate(range(min = 1, max = 100, code = "limit_range", message = "limit must be between 1 and 100"))]
+    pub limit: Option<usize>,
+
+    /// Match customer names by edit distance instead of words
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+/// Query parameters for paging through an order's status history
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct HistoryQuery {
+    #[validate(range(min = 1, max = 100, code = "limit_range", message = "limit must be between 1 and 100"))]
+    pub limit: Option<usize>,
+
+    /// Only entries recorded strictly after this instant
+    pub after: Option<DateTime<Utc>>,
+
+    /// Only entries after this version; pass the last entry's `version` to get the next page.
+    /// Unlike `after`, it never skips entries that share a timestamp.
+    pub after_version: Option<usize>,
+
+    /// Only entries that moved the order into this status
+    #[validate(regex(path = "STATUS_REGEX", code = "invalid_status", message = "invalid status"))]
+    pub to: Option<String>,
+}
+
+/// Response DTO
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OrderResponseDto {
     pub id: Uuid,
     pub customer: String,
     pub items: Vec<String>,
     pub status: String,
+    pub assignee: Option<String>,
+    pub tags: BTreeSet<String>,
+    pub frozen: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 impl From<Order> for OrderResponseDto {
@@ -35,15 +518,173 @@ impl From<Order> for OrderResponseDto {
         Self {
             id: o.id,
             customer: o.customer,
-            items: o.items,
+            items: o.items.iter().map(|item| item.to_string()).collect(),
             status: o.status,
+            assignee: o.assignee,
+            tags: o.tags,
+            frozen: o.frozen,
+            created_at: o.created_at,
+            updated_at: o.updated_at,
+        }
+    }
+}
+
+/// Paginated list body: `{ "items": [...], "page": {...} }`
+#[derive(Debug, Serialize)]
+pub struct OrderListDto {
+    pub items: Vec<OrderResponseDto>,
+    pub page: PageDto,
+    /// Store version the page was read at, for its ETag
+    #[serde(skip)]
+    pub version: u64,
+}
+
+impl OrderListDto {
+    /// Every result on one page, for endpoints without paging; they send no ETag, so no version
+    pub fn single_page(items: Vec<OrderResponseDto>, limit: Option<usize>) -> Self {
+        let page = PageDto { limit, next_cursor: None, total: items.len() };
+        Self { items, page, version: 0 }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageDto {
+    pub limit: Option<usize>,
+    /// Offset to pass for the next page, None on the last one
+    pub next_cursor: Option<String>,
+    /// Matching orders across all pages
+    pub total: usize,
+}
+
+/// Versions to compare: 0 is the order as created, n is the order after its n-th status change
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Result set of an unpaginated endpoint, cut off at the configured maximum
+#[derive(Debug)]
+pub struct Capped<T> {
+    pub items: Vec<T>,
+    pub truncated: bool,
+}
+
+impl<T> Capped<T> {
+    pub fn new(mut items: Vec<T>, max: Option<usize>) -> Self {
+        let truncated = max.is_some_and(|max| items.len() > max);
+        if let Some(max) = max {
+            items.truncate(max);
         }
+        Self { items, truncated }
     }
 }
 
+/// Query params for PATCH: `update_mask` lists the only body fields to apply, comma separated
+#[derive(Debug, Deserialize)]
+pub struct PatchQuery {
+    pub update_mask: Option<String>,
+}
+
+/// Query params for a status change
+#[derive(Debug, Default, Deserialize)]
+pub struct StatusQuery {
+    /// Setting the status the order already has is a no-op instead of a conflict
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+/// How `POST /admin/restore` treats invalid lines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    /// Any invalid line rejects the whole payload and nothing is restored
+    #[default]
+    Atomic,
+    /// Valid lines are restored, invalid ones are skipped and reported
+    BestEffort,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreQuery {
+    #[serde(default)]
+    pub mode: RestoreMode,
+}
+
+/// Query params for the event log: only events after sequence number `since` are returned
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    #[serde(default)]
+    pub since: u64,
+}
+
+/// Fields that differ between two versions of an order
+#[derive(Debug, Serialize)]
+pub struct OrderDiffDto {
+    pub from: usize,
+    pub to: usize,
+    pub changes: BTreeMap<String, FieldChangeDto>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldChangeDto {
+    pub from: String,
+    pub to: String,
+}
+
+/// Response DTO for a status history entry
+#[derive(Debug, Serialize)]
+pub struct StatusChangeDto {
+    /// Order version this change produced, numbered as in `/diff`: the first change makes version 1
+    pub version: usize,
+    pub from: String,
+    pub to: String,
+    pub at: DateTime<Utc>,
+    pub by: Option<String>,
+}
+
+impl StatusChangeDto {
+    pub fn new(version: usize, c: StatusChange) -> Self {
+        Self {
+            version,
+            from: c.from,
+            to: c.to,
+            at: c.at,
+            by: c.by,
+        }
+    }
+}
+
+/// How long orders have sat in one status, in seconds since their last update.
+/// The ages are None when no order currently has the status.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct AgeStatsDto {
+    pub count: usize,
+    pub min_secs: Option<f64>,
+    pub avg_secs: Option<f64>,
+    pub max_secs: Option<f64>,
+}
+
+/// Store internals for debugging memory use
+#[derive(Debug, Serialize)]
+pub struct StoreStatsDto {
+    pub orders: usize,
+    /// Rough heap and inline size of the stored orders, in bytes
+    pub approx_bytes: usize,
+    /// Distinct interned item names
+    pub item_names: usize,
+    /// Entries in the create dedup window
+    pub recent_creates: usize,
+    pub version: u64,
+}
+
 lazy_static::lazy_static! {
     static ref STATUS_REGEX: regex::Regex =
         regex::Regex::new(r"^(pending|shipped|delivered|cancelled)$").unwrap();
+    static ref SORT_REGEX: regex::Regex =
+        regex::Regex::new(r"^-?(customer|status)$").unwrap();
+    static ref TEMPLATE_NAME_REGEX: regex::Regex =
+        regex::Regex::new(r"^[A-Za-z0-9_-]{1,64}$").unwrap();
 }
 
 #[cfg(test)]
@@ -75,12 +716,152 @@ mod tests {
         assert!(invalid_dto.validate().is_err());
     }
 
+    #[test]
+    fn test_customer_max_length() {
+        let dto = |len: usize| CreateOrderDto {
+            customer: "é".repeat(len),
+            items: vec!["Item 1".to_string()],
+        };
+        assert!(dto(200).validate().is_ok());
+
+        let errors = dto(201).validate().unwrap_err();
+        assert_eq!(errors.field_errors()["customer"][0].code, "customer_too_long");
+
+        let patch: OrderPatchDto = serde_json::from_value(serde_json::json!({
+            "customer": "x".repeat(201), "items": ["Item 1"], "status": "pending", "assignee": null
+        }))
+        .unwrap();
+        assert_eq!(patch.validate().unwrap_err().field_errors()["customer"][0].code, "customer_too_long");
+
+        let limits = LengthLimits { customer: 10, ..LengthLimits::default() };
+        assert!(LIMITS.sync_scope(limits, || dto(10).validate()).is_ok());
+        let errors = LIMITS.sync_scope(limits, || dto(11).validate()).unwrap_err();
+        assert_eq!(errors.field_errors()["customer"][0].params["max"], 10);
+    }
+
+    #[test]
+    fn test_item_max_length() {
+        let dto = |last: usize| CreateOrderDto {
+            customer: "John Doe".to_string(),
+            items: vec!["Item 1".to_string(), "é".repeat(last)],
+        };
+        assert!(dto(256).validate().is_ok());
+
+        let errors = dto(257).validate().unwrap_err();
+        let error = &errors.field_errors()["items"][0];
+        assert_eq!(error.code, "item_too_long");
+        assert_eq!(error.params["index"], 1);
+
+        let limits = LengthLimits { item: 8, ..LengthLimits::default() };
+        assert!(LIMITS.sync_scope(limits, || dto(8).validate()).is_ok());
+        let errors = LIMITS.sync_scope(limits, || dto(9).validate()).unwrap_err();
+        assert_eq!(errors.field_errors()["items"][0].params["max"], 8);
+    }
+
+    #[test]
+    fn test_list_query_conflicts() {
+        let query = |q: &str| serde_json::from_str::<ListQuery>(q).unwrap().conflict();
+
+        assert!(query(r#"{"status":"pending","sort":"-status"}"#).unwrap().starts_with("sort=status conflicts"));
+        assert!(query(r#"{"offset":20}"#).unwrap().starts_with("offset requires limit"));
+        assert_eq!(query(r#"{"status":"pending","sort":"customer","limit":20,"offset":20}"#), None);
+        assert_eq!(query(r#"{"sort":"status"}"#), None);
+
+        let inverted = r#"{"created_after":"2024-02-01T00:00:00Z","created_before":"2024-01-01T00:00:00Z"}"#;
+        assert!(query(inverted).unwrap().starts_with("created_before must be later"));
+        assert_eq!(query(r#"{"created_before":"2024-01-01T00:00:00Z"}"#), None);
+    }
+
+    #[test]
+    fn test_item_title_case() {
+        let title = ItemNormalization::parse("titlecase", "USB, HDMI").unwrap();
+        assert_eq!(title.apply("  widget pro ".to_string()), "Widget Pro");
+        assert_eq!(title.apply("usb CABLE".to_string()), "USB Cable");
+        assert_eq!(title.apply("éclair".to_string()), "Éclair");
+        assert_eq!(ItemNormalization::None.apply("widget pro".to_string()), "widget pro");
+        assert_eq!(ItemNormalization::parse("upper", ""), None);
+    }
+
+    #[test]
+    fn test_order_template_validation() {
+        let template = |name: &str, items: Vec<&str>| OrderTemplateDto {
+            name: name.to_string(),
+            customer: "Jane".to_string(),
+            items: items.into_iter().map(str::to_string).collect(),
+        };
+        assert!(template("monthly-coffee_2", vec!["Beans"]).validate().is_ok());
+
+        let errors = template("monthly coffee", vec![]).validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert_eq!(fields["name"][0].code, "invalid_template_name");
+        assert_eq!(fields["items"][0].code, "items_empty");
+        assert!(template(&"x".repeat(65), vec!["Beans"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_minimal_bodies_take_defaults() {
+        let dto: InstantOrderDto = serde_json::from_str(r#"{"customer":"Jane","items":["A"]}"#).unwrap();
+        assert!(dto.status.is_none());
+        let dto: UpdateStatusDto = serde_json::from_str(r#"{"status":"shipped"}"#).unwrap();
+        assert!(dto.expected.is_none());
+        let dto: BulkTagDto = serde_json::from_str(r#"{"filter":{"tag":"promo"}}"#).unwrap();
+        assert!(dto.add.is_empty() && dto.remove.is_empty());
+        let dto: DeliveredDto = serde_json::from_str("{}").unwrap();
+        assert!(dto.by.is_none());
+
+        // Required fields still have to be there
+        for body in [r#"{"customer":"Jane"}"#, r#"{"items":["A"]}"#] {
+            let error = serde_json::from_str::<CreateOrderDto>(body).unwrap_err();
+            assert!(error.to_string().starts_with("missing field"), "{error}");
+        }
+        assert!(serde_json::from_str::<UpdateStatusDto>(r#"{"expected":"pending"}"#).is_err());
+        assert!(serde_json::from_str::<BulkTagDto>(r#"{"add":["promo"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_instant_order_dto_deserialization() {
+        let json = r#"{"customer":"John Doe","items":["Item 1"],"status":"cancelled"}"#;
+        let dto: InstantOrderDto = serde_json::from_str(json).unwrap();
+        assert_eq!(dto.customer, "John Doe");
+        assert_eq!(dto.status.as_deref(), Some("cancelled"));
+        assert!(dto.validate().is_ok());
+
+        let json = r#"{"customer":"John Doe","items":["Item 1"],"status":"lost"}"#;
+        let dto: InstantOrderDto = serde_json::from_str(json).unwrap();
+        assert!(dto.validate().is_err());
+
+        // Create rules apply as well
+        let json = r#"{"customer":"","items":["Item 1"]}"#;
+        let dto: InstantOrderDto = serde_json::from_str(json).unwrap();
+        assert!(dto.validate().unwrap_err().field_errors().contains_key("customer"));
+    }
+
+    #[test]
+    fn test_order_patch_dto_validation() {
+        let dto: OrderPatchDto = serde_json::from_str(
+            r#"{"customer":"John Doe","items":["Item 1"],"status":"shipped","assignee":null}"#,
+        )
+        .unwrap();
+        assert!(dto.validate().is_ok());
+
+        let dto: OrderPatchDto = serde_json::from_str(
+            r#"{"customer":"","items":[],"status":"lost","assignee":""}"#,
+        )
+        .unwrap();
+        let errors = dto.validate().unwrap_err();
+        assert_eq!(errors.field_errors().len(), 4);
+
+        // Required fields cannot be missing
+        assert!(serde_json::from_str::<OrderPatchDto>(r#"{"items":["Item 1"],"status":"pending"}"#).is_err());
+    }
+
     #[test]
     fn test_update_status_dto_validation() {
         // Valid statuses
         for status in &["pending", "shipped", "delivered", "cancelled"] {
             let dto = UpdateStatusDto {
                 status: status.to_string(),
+                expected: None,
             };
             assert!(dto.validate().is_ok(), "Status '{}' should be valid", status);
         }
@@ -88,25 +869,142 @@ mod tests {
         // Invalid status
         let invalid_dto = UpdateStatusDto {
             status: "invalid_status".to_string(),
+            expected: None,
         };
         assert!(invalid_dto.validate().is_err());
     }
 
     #[test]
-    fn test_order_response_dto_from_order() {
-        let order = Order {
-            id: Uuid::new_v4(),
-            customer: "Test Customer".to_string(),
-            items: vec!["Item 1".to_string(), "Item 2".to_string()],
-            status: "pending".to_string(),
+    fn test_list_query_validation() {
+        // Fully specified valid query
+        let valid_query = ListQuery {
+            status: Some("shipped".to_string()),
+            assignee: Some("alice".to_string()),
+            item: Some("Widget".to_string()),
+            sort: Some("-customer".to_string()),
+            limit: Some(10),
+            offset: Some(20),
+            created: CreatedRange::default(),
         };
+        assert!(valid_query.validate().is_ok());
+
+        // No filters at all is valid too
+        assert!(ListQuery::default().validate().is_ok());
+
+        // Invalid status, sort field and limit are all reported
+        let invalid_query = ListQuery {
+            status: Some("unknown".to_string()),
+            sort: Some("id".to_string()),
+            limit: Some(0),
+            ..Default::default()
+        };
+        let errors = invalid_query.validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert!(fields.contains_key("status"));
+        assert!(fields.contains_key("sort"));
+        assert!(fields.contains_key("limit"));
+    }
+
+    #[test]
+    fn test_history_query_validation() {
+        let valid_query: HistoryQuery = serde_json::from_str(
+            r#"{"limit":5,"after":"2024-01-01T00:00:00Z","to":"shipped"}"#,
+        )
+        .unwrap();
+        assert!(valid_query.validate().is_ok());
+        assert!(valid_query.after.is_some());
+
+        let invalid_query = HistoryQuery {
+            limit: Some(0),
+            after: None,
+            after_version: None,
+            to: Some("lost".to_string()),
+        };
+        let errors = invalid_query.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("limit"));
+        assert!(errors.field_errors().contains_key("to"));
+    }
+
+    #[test]
+    fn test_csv_row_into_create_dto() {
+        let row = CsvOrderRow {
+            customer: " Jane ".to_string(),
+            items: "Book | Pen||".to_string(),
+        };
+        let dto = CreateOrderDto::from(row);
+        assert_eq!(dto.customer, "Jane");
+        assert_eq!(dto.items, ["Book", "Pen"]);
+    }
+
+    #[test]
+    fn test_search_query_validation() {
+        let query: SearchQuery = serde_json::from_str(r#"{"q":"widget"}"#).unwrap();
+        assert!(query.validate().is_ok());
+
+        let query: SearchQuery = serde_json::from_str(r#"{"q":"","limit":0}"#).unwrap();
+        let errors = query.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("q"));
+        assert!(errors.field_errors().contains_key("limit"));
+    }
+
+    #[test]
+    fn test_bulk_tag_dto() {
+        let dto: BulkTagDto = serde_json::from_str(r#"{"filter":{"status":"pending"},"add":["promo"]}"#).unwrap();
+        assert!(dto.validate().is_ok());
+        assert!(dto.remove.is_empty());
+
+        let dto: BulkTagDto = serde_json::from_str(r#"{"filter":{"status":"pending"},"add":[" "]}"#).unwrap();
+        assert!(dto.validate().unwrap_err().field_errors().contains_key("add"));
+    }
+
+    #[test]
+    fn test_order_filter() {
+        assert!(OrderFilter::default().is_empty());
+
+        let mut order = Order::new("John Doe".to_string(), vec!["Item 1".to_string()], Utc::now());
+        order.created_at = "2024-01-15T00:00:00Z".parse().unwrap();
+
+        let filter: OrderFilter = serde_json::from_str(r#"{"created_before":"2024-02-01T00:00:00Z"}"#).unwrap();
+        assert!(!filter.is_empty());
+        assert!(filter.matches(&order));
+
+        let filter: OrderFilter = serde_json::from_str(r#"{"status":"pending","created_before":"2024-01-01T00:00:00Z"}"#).unwrap();
+        assert!(!filter.matches(&order));
+
+        let filter: OrderFilter = serde_json::from_str(r#"{"status":"pending"}"#).unwrap();
+        assert!(filter.matches(&order));
+    }
+
+    #[test]
+    fn test_order_response_dto_from_order() {
+        let order = Order::new(
+            "Test Customer".to_string(),
+            vec!["Item 1".to_string(), "Item 2".to_string()], Utc::now(),
+        );
 
         let response_dto = OrderResponseDto::from(order.clone());
 
         assert_eq!(response_dto.id, order.id);
         assert_eq!(response_dto.customer, order.customer);
-        assert_eq!(response_dto.items, order.items);
+        assert_eq!(response_dto.items, ["Item 1", "Item 2"]);
         assert_eq!(response_dto.status, order.status);
+        assert_eq!(response_dto.assignee, order.assignee);
+    }
+
+    #[test]
+    fn test_assignee_dto() {
+        let dto: AssigneeDto = serde_json::from_str(r#"{"assignee":"alice"}"#).unwrap();
+        assert_eq!(dto.assignee.as_deref(), Some("alice"));
+        assert!(dto.validate().is_ok());
+
+        // null and a missing field both clear the assignee
+        let dto: AssigneeDto = serde_json::from_str(r#"{"assignee":null}"#).unwrap();
+        assert!(dto.assignee.is_none());
+        let dto: AssigneeDto = serde_json::from_str("{}").unwrap();
+        assert!(dto.assignee.is_none());
+
+        let dto: AssigneeDto = serde_json::from_str(r#"{"assignee":""}"#).unwrap();
+        assert!(dto.validate().is_err());
     }
 
     #[test]
@@ -116,6 +1014,11 @@ mod tests {
             customer: "Test Customer".to_string(),
             items: vec!["Product A".to_string()],
             status: "shipped".to_string(),
+            assignee: None,
+            tags: BTreeSet::from(["promo".to_string()]),
+            frozen: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
         };
 
         let json = serde_json::to_string(&response_dto).unwrap();
@@ -136,4 +1039,16 @@ mod tests {
         assert!(!STATUS_REGEX.is_match(""));
         assert!(!STATUS_REGEX.is_match("pending "));
     }
+
+    #[test]
+    fn test_sort_regex() {
+        assert!(SORT_REGEX.is_match("customer"));
+        assert!(SORT_REGEX.is_match("-customer"));
+        assert!(SORT_REGEX.is_match("status"));
+        assert!(SORT_REGEX.is_match("-status"));
+
+        assert!(!SORT_REGEX.is_match("id"));
+        assert!(!SORT_REGEX.is_match("--status"));
+        assert!(!SORT_REGEX.is_match(""));
+    }
 }
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::errors::ApiError;
+
+/// Runs CPU-heavy jobs on the blocking thread pool, at most `workers` at a time.
+/// Up to `queue` more may wait for a worker; anything beyond that is shed with a 503.
+#[derive(Debug, Clone)]
+pub struct BlockingPool {
+    admitted: Arc<Semaphore>,
+    running: Arc<Semaphore>,
+}
+
+impl BlockingPool {
+    pub fn new(workers: usize, queue: usize) -> Self {
+        let workers = workers.max(1);
+        Self {
+            admitted: Arc::new(Semaphore::new(workers + queue)),
+            running: Arc::new(Semaphore::new(workers)),
+        }
+    }
+
+    pub async fn run<T, F>(&self, job: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let _admitted = self
+            .admitted
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| ApiError::Unavailable("too many background jobs, retry shortly".into()))?;
+        let running = self.running.clone().acquire_owned().await.map_err(|_| ApiError::internal("blocking pool closed"))?;
+        tokio::task::spawn_blocking(move || {
+            let _running = running;
+            job()
+        })
+        .await
+        .map_err(|e| ApiError::internal(format_args!("blocking job failed: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_saturated_pool_sheds_load() {
+        let pool = BlockingPool::new(1, 1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.run(move || blocked.recv().unwrap()).await }
+        });
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.run(|| 2).await }
+        });
+        while pool.admitted.available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let shed = pool.run(|| 3).await;
+        assert!(matches!(shed, Err(ApiError::Unavailable(_))));
+
+        release.send(()).unwrap();
+        busy.await.unwrap().unwrap();
+        assert_eq!(queued.await.unwrap().unwrap(), 2);
+        assert_eq!(pool.run(|| 4).await.unwrap(), 4);
+    }
+}
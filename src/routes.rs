@@ -1,54 +1,2503 @@
 use axum::{
-    extract::{Path, State}, http, routing::{get, post, put}, Json, Router
+    body::{Body, Bytes}, extract::{Path, Query, Request, State}, Extension, http::{self, header, HeaderName, HeaderValue, Method, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing::{get, patch, post, put}, Json, Router
+};
+use futures_util::{stream, Stream, StreamExt};
+use tracing::Instrument;
+use serde_json::json;
+use std::{collections::BTreeMap, fmt::Write, hash::{DefaultHasher, Hash, Hasher}, sync::Arc, time::Duration};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
 };
-use tower_http::trace::TraceLayer;
-use uuid::Uuid;
 
 use crate::{
-    db::{self, Db}, errors::ApiError, order_dtos::{CreateOrderDto, OrderResponseDto, UpdateStatusDto}
+    config::Config, db::{self, Db}, errors::{self, ApiError}, events::{EventRecord, EventSnapshot}, extractors::{JsonBody, JsonMode, OrderId, PreferMinimal, ValidatedQuery}, i18n, logging::{self, LogSampler}, models::Order, pool::BlockingPool, timeouts, order_dtos::{self, AgeStatsDto, AssigneeDto, BulkStatusDto, BulkTagDto, Capped, CreateOrderDto, CreatedRange, CsvExportRow, CsvOrderRow, DeliveredDto, DiffQuery, EventsQuery, HistoryQuery, ImportRowErrorDto, ImportSummaryDto, InstantOrderDto, ListQuery, OrderDiffDto, OrderListDto, OrderFilter, OrderResponseDto, OrderTemplateDto, PatchQuery, RestoreMode, RestoreQuery, SearchQuery, StatusCase, StatusChangeDto, StatusQuery, StoreStatsDto, UpdateStatusDto}
 };
 
-pub fn app(db: Db) -> Router {
-    Router::new()
-        .route("/orders", post(create).get(list))
-        .route("/orders/{id}", get(get_one).delete(delete_one))
+pub fn app(db: Db, config: &Config) -> Router {
+    // Only order reads are cacheable; stats, metrics, events, backups and exports must stay fresh
+    let cache = middleware::from_fn_with_state(config.cache_max_age, cache_headers);
+    let mut router = Router::new()
+        .route("/", get(root))
+        .route("/orders", post(create).merge(get(list).layer(cache.clone())))
+        .route("/orders/instant", post(create_instant))
+        .route("/orders/bulk", post(bulk_create))
+        .route("/orders/bulk-delete", post(bulk_delete))
+        .route("/orders/bulk-tag", post(bulk_tag))
+        .route("/orders/bulk-status/validate", post(preflight_bulk_status))
+        .route("/orders/search", get(search))
+        .route("/orders/import.csv", post(import_csv))
+        .route("/orders/export.csv", get(export_csv))
+        .route("/orders/{id}", get(get_one).head(head_one).layer(cache).merge(patch(merge_patch).delete(delete_one)))
         .route("/orders/{id}/status", put(update_status))
-        .with_state(db)
+        .route("/orders/{id}/delivered", post(mark_delivered))
+        .route("/orders/{id}/assignee", put(set_assignee))
+        .route("/orders/{id}/freeze", post(freeze))
+        .route("/orders/{id}/unfreeze", post(unfreeze))
+        .route("/orders/{id}/restore", post(undelete))
+        .route("/orders/{id}/history", get(history))
+        .route("/orders/{id}/transitions", get(transitions))
+        .route("/orders/{id}/diff", get(diff))
+        .route("/templates", post(save_template).get(list_templates))
+        .route("/templates/{name}/instantiate", post(instantiate_template))
+        .route("/events", get(events))
+        .route("/events/snapshot", get(event_snapshot))
+        .route("/schema", get(schema_index))
+        .route("/schema/{dto}", get(schema))
+        .route("/admin/order-ages", get(order_ages))
+        .route("/admin/stats", get(store_stats))
+        .route("/metrics", get(metrics))
+        .route("/admin/backup.jsonl", get(backup_jsonl))
+        .route("/admin/export.zip", get(export_zip))
+        .route("/admin/restore", post(restore_jsonl))
+        .route("/admin/rebuild", post(rebuild));
+    if !config.enable_writes {
+        // Only matched routes, so unknown paths still 404
+        router = router.route_layer(middleware::from_fn(reject_writes));
+    }
+    let pool = BlockingPool::new(config.blocking_workers, config.blocking_queue);
+    let router = router
+        .layer(Extension(pool))
+        .layer(Extension(JsonMode { strict: config.strict_json }))
+        .layer(Extension(config.status_case));
+    with_middleware(router.fallback(unknown_route).with_state(db), config)
+}
+
+/// Layers wrapped around every route, outermost last
+fn with_middleware(router: Router, config: &Config) -> Router {
+    let sampler = Arc::new(LogSampler::new(config.log_sample_rate, config.log_slow_threshold));
+    let expose_panics = config.dev_logging;
+    let budgets = Arc::new(config.latency_budgets.clone());
+    let timeouts = Arc::new(config.request_timeouts.clone());
+    let router = if config.require_https { router.layer(middleware::from_fn(require_https)) } else { router };
+    let router = router
+        .layer(middleware::from_fn_with_state(timeouts, timeouts::enforce_timeouts))
+        .layer(middleware::from_fn_with_state(budgets, logging::enforce_budgets))
+        .layer(middleware::from_fn(i18n::negotiate_language))
+        .layer(middleware::from_fn_with_state(config.error_format, errors::use_format))
+        .layer(middleware::from_fn_with_state(config.length_limits, order_dtos::use_limits))
+        // Inside the trace span, so the panic log carries the request id
+        .layer(CatchPanicLayer::custom(move |payload| errors::panic_response(payload, expose_panics)))
         .layer(
             TraceLayer::new_for_http()
-                .on_request(|request: &http::Request<_>, _span: &tracing::Span| {
-                    tracing::info!("Incoming: {} {}", request.method(), request.uri());
+                // The span carries method, uri and request id, so only the sampled response line is logged
+                .make_span_with(|request: &http::Request<_>| {
+                    let request_id = request
+                        .headers()
+                        .get(REQUEST_ID)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default();
+                    tracing::info_span!("request", method = %request.method(), uri = %request.uri(), request_id)
                 })
-                .on_response(|response: &http::Response<_>, latency: std::time::Duration, _span: &tracing::Span| {
-                    tracing::info!("Response: {} (took {:?})", response.status(), latency);
+                .on_request(())
+                .on_response(move |response: &http::Response<_>, latency: std::time::Duration, _span: &tracing::Span| {
+                    if sampler.should_log(response.status(), latency) {
+                        tracing::info!("Response: {} (took {:?})", response.status(), latency);
+                    }
                 })
         )
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(REQUEST_ID)))
+        .layer(SetRequestIdLayer::new(HeaderName::from_static(REQUEST_ID), MakeRequestUuid));
+    match cors_layer(config) {
+        // Outermost, so preflights are answered before anything else runs
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
 }
 
-async fn create(State(db): State<Db>, Json(payload): Json<CreateOrderDto>) -> Result<Json<OrderResponseDto>, ApiError> {
+/// Browser cross-origin access, or None when no origin is configured
+fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    if config.cors_origins.is_empty() {
+        return None;
+    }
+    let any_origin = config.cors_origins.iter().any(|origin| origin == "*");
+    // `Config::check` refuses `*` with credentials; should it get here anyway, only listed origins are granted
+    let origin = if any_origin && !config.cors_allow_credentials {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.cors_origins.iter().filter(|origin| *origin != "*").filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
+    let mut cors = CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(config.cors_allow_credentials);
+    if let Some(max_age) = config.cors_max_age {
+        cors = cors.max_age(max_age);
+    }
+    Some(cors)
+}
+
+/// Header carrying the request id; a client supplied one is kept
+const REQUEST_ID: &str = "x-request-id";
+
+async fn unknown_route() -> ApiError {
+    ApiError::NotFound { resource: "route" }
+}
+
+/// Read-only mode: anything but a read is refused
+async fn reject_writes(request: Request, next: Next) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        next.run(request).await
+    } else {
+        ApiError::Forbidden("writes are disabled".into()).into_response()
+    }
+}
+
+/// Refuses requests the load balancer did not receive over HTTPS, judged by the first `X-Forwarded-Proto`
+/// entry. `/` stays reachable so plain HTTP health checks keep working.
+async fn require_https(request: Request, next: Next) -> Response {
+    let proto = request
+        .headers()
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(str::trim);
+    if request.uri().path() == "/" || proto.is_some_and(|proto| proto.eq_ignore_ascii_case("https")) {
+        next.run(request).await
+    } else {
+        ApiError::BadRequest("HTTPS is required".into()).into_response()
+    }
+}
+
+/// Lets clients and CDNs cache successful reads for `max_age`; layered only on the order read routes
+async fn cache_headers(State(max_age): State<Option<Duration>>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        if let Some(max_age) = max_age {
+            let value = HeaderValue::from_str(&format!("max-age={}", max_age.as_secs())).expect("digits are a valid header value");
+            headers.insert(header::CACHE_CONTROL, value);
+        }
+        headers.append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    response
+}
+
+async fn create(
+    State(db): State<Db>,
+    PreferMinimal(minimal): PreferMinimal,
+    JsonBody(payload): JsonBody<CreateOrderDto>,
+) -> Result<Response, ApiError> {
     let order = db::create_order(db, payload).await?;
+    Ok(written(order, minimal, true))
+}
+
+const PREFERENCE_APPLIED: &str = "preference-applied";
+
+/// The written order, or under `Prefer: return=minimal` an empty 201 with a Location (create) or 204 (update)
+fn written(order: OrderResponseDto, minimal: bool, created: bool) -> Response {
+    if !minimal {
+        return Json(order).into_response();
+    }
+    let applied = [(PREFERENCE_APPLIED, "return=minimal")];
+    if created {
+        (StatusCode::CREATED, applied, [(header::LOCATION, format!("/orders/{}", order.id))]).into_response()
+    } else {
+        (StatusCode::NO_CONTENT, applied).into_response()
+    }
+}
+
+async fn save_template(State(db): State<Db>, JsonBody(payload): JsonBody<OrderTemplateDto>) -> Result<Json<OrderTemplateDto>, ApiError> {
+    Ok(Json(db::save_template(db, payload).await?))
+}
+
+async fn list_templates(State(db): State<Db>) -> Json<Vec<OrderTemplateDto>> {
+    Json(db::list_templates(db).await)
+}
+
+async fn instantiate_template(
+    State(db): State<Db>,
+    Path(name): Path<String>,
+    PreferMinimal(minimal): PreferMinimal,
+) -> Result<Response, ApiError> {
+    let order = db::instantiate_template(db, &name).await?;
+    Ok(written(order, minimal, true))
+}
+
+async fn create_instant(State(db): State<Db>, JsonBody(payload): JsonBody<InstantOrderDto>) -> Result<Json<OrderResponseDto>, ApiError> {
+    let order = db::create_instant_order(db, payload).await?;
     Ok(Json(order))
 }
 
-async fn get_one(State(db): State<Db>, Path(id): Path<Uuid>) -> Result<Json<OrderResponseDto>, ApiError> {
+async fn bulk_delete(State(db): State<Db>, JsonBody(filter): JsonBody<OrderFilter>) -> Result<Json<serde_json::Value>, ApiError> {
+    let deleted = db::bulk_delete(db, filter).await?;
+    Ok(Json(json!({ "deleted": deleted })))
+}
+
+/// Creates one order per CSV row (`customer,items` header, items pipe-separated), skipping rows that fail
+async fn import_csv(State(db): State<Db>, body: Bytes) -> Result<Json<ImportSummaryDto>, ApiError> {
+    let mut reader = csv::Reader::from_reader(body.as_ref());
+    let headers = reader.headers().map_err(|e| ApiError::BadRequest(e.to_string()))?.clone();
+    if !["customer", "items"].iter().all(|column| headers.iter().any(|h| h == *column)) {
+        return Err(ApiError::BadRequest("CSV header must name customer and items columns".into()));
+    }
+
+    let mut summary = ImportSummaryDto::default();
+    for record in reader.records() {
+        // Rows that fail to parse carry their position on the error instead
+        let position = match &record {
+            Ok(r) => r.position(),
+            Err(e) => e.position(),
+        };
+        let line = position.map(|p| p.line()).unwrap_or_default();
+        let row = record
+            .and_then(|r| r.deserialize::<CsvOrderRow>(Some(&headers)))
+            .map_err(|e| e.to_string());
+        let result = match row {
+            Ok(row) => db::create_order(db.clone(), row.into()).await.map_err(|e| match e {
+                ApiError::Validation(errors) => errors.to_string(),
+                other => other.to_string(),
+            }),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(order) => summary.imported.push(order.id),
+            Err(error) => summary.errors.push(ImportRowErrorDto { line, error }),
+        }
+    }
+    Ok(Json(summary))
+}
+
+/// All orders as CSV, written on the blocking pool so a big store doesn't stall the runtime
+async fn export_csv(
+    State(db): State<Db>,
+    Extension(pool): Extension<BlockingPool>,
+    headers: http::HeaderMap,
+    ValidatedQuery(range): ValidatedQuery<CreatedRange>,
+) -> Result<Response, ApiError> {
+    let (version, orders) = db::snapshot_orders_at(db, checked(range)?).await;
+    let csv = pool.run(move || write_csv(orders.iter().map(CsvExportRow::from))).await??;
+    // The export is rebuilt per request but is the same bytes for the same store version,
+    // so the version-based ETag lets a resumed download check nothing changed in between
+    let etag = format!("\"v{version}-export\"");
+    let if_range = headers.get(header::IF_RANGE).map(|v| v.to_str().unwrap_or_default());
+    let range = headers.get(header::RANGE).filter(|_| if_range.is_none_or(|tag| tag == etag));
+    let range = range.map(|range| byte_range(range.to_str().unwrap_or_default(), csv.len())).transpose()?.flatten();
+    let Some(bytes) = range else {
+        return Ok(([(header::CONTENT_TYPE, CSV.to_string()), (header::ACCEPT_RANGES, "bytes".to_string()), (header::ETAG, etag)], csv).into_response());
+    };
+    let headers = [
+        (header::CONTENT_TYPE, CSV.to_string()),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+        (header::ETAG, etag),
+        (header::CONTENT_RANGE, format!("bytes {}-{}/{}", bytes.start(), bytes.end(), csv.len())),
+    ];
+    Ok((StatusCode::PARTIAL_CONTENT, headers, csv[bytes].to_vec()).into_response())
+}
+
+/// The bytes a single-range `Range` header (`bytes=0-99`, `bytes=100-` or `bytes=-50`) asks for
+/// out of `length`. Other units, multiple ranges and malformed ranges are ignored (None), so the
+/// full body is served; only a well-formed range that can't be satisfied is refused.
+fn byte_range(range: &str, length: usize) -> Result<Option<std::ops::RangeInclusive<usize>>, ApiError> {
+    let Some((start, end)) = range.trim().strip_prefix("bytes=").filter(|spec| !spec.contains(',')).and_then(|spec| spec.split_once('-')) else {
+        return Ok(None);
+    };
+    let parse = |n: &str| n.trim().parse::<usize>().ok();
+    let wanted = match (start.trim(), end.trim()) {
+        ("", suffix) => parse(suffix).map(|n| (length.saturating_sub(n), n.min(length))),
+        (start, "") => parse(start).map(|start| (start, length.saturating_sub(start))),
+        (start, end) => match parse(start).zip(parse(end)) {
+            Some((start, end)) if start <= end => Some((start, (end + 1).min(length).saturating_sub(start))),
+            _ => None,
+        },
+    };
+    match wanted {
+        None => Ok(None),
+        Some((start, count)) if start < length && count > 0 => Ok(Some(start..=start + count - 1)),
+        Some(_) => Err(ApiError::RangeNotSatisfiable { length }),
+    }
+}
+
+const CSV: &str = "text/csv";
+
+/// The export's date range, unless its bounds are inverted
+fn checked(range: CreatedRange) -> Result<CreatedRange, ApiError> {
+    match range.conflict() {
+        Some(conflict) => Err(ApiError::BadRequest(conflict)),
+        None => Ok(range),
+    }
+}
+
+/// Rows under the export's header line, shared by the export and CSV lists
+fn write_csv(rows: impl IntoIterator<Item = CsvExportRow>) -> Result<Vec<u8>, ApiError> {
+    let write = || {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut empty = true;
+        for row in rows {
+            writer.serialize(row)?;
+            empty = false;
+        }
+        // The header comes from the first row, so an empty export writes it on its own
+        if empty {
+            writer.write_record(CsvExportRow::HEADER)?;
+        }
+        writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))
+    };
+    write().map_err(|e| ApiError::internal(format_args!("CSV export failed: {e}")))
+}
+
+/// One JSON file of orders per customer, zipped on the blocking pool
+async fn export_zip(
+    State(db): State<Db>,
+    Extension(pool): Extension<BlockingPool>,
+    ValidatedQuery(range): ValidatedQuery<CreatedRange>,
+) -> Result<Response, ApiError> {
+    let orders = db::snapshot_orders(db, checked(range)?).await;
+    let zip = pool.run(move || zip_by_customer(orders)).await?.map_err(|e| ApiError::internal(format_args!("ZIP export failed: {e}")))?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/zip"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=\"orders.zip\""),
+    ];
+    Ok((headers, zip).into_response())
+}
+
+fn zip_by_customer(orders: Vec<Order>) -> zip::result::ZipResult<Vec<u8>> {
+    use std::io::Write as _;
+
+    let mut by_customer: BTreeMap<String, Vec<OrderResponseDto>> = BTreeMap::new();
+    for order in orders {
+        by_customer.entry(order.customer.clone()).or_default().push(order.into());
+    }
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut taken = std::collections::HashSet::new();
+    for (customer, orders) in by_customer {
+        zip.start_file(customer_filename(&customer, &mut taken), options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&orders).map_err(std::io::Error::from)?)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// `<name>.json` safe on any file system: only ASCII letters, digits, `-` and `_`, at most 64 of them.
+/// Customers that end up with the same name get a `-2`, `-3`, ... suffix.
+fn customer_filename(customer: &str, taken: &mut std::collections::HashSet<String>) -> String {
+    let mut stem: String = customer
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .take(64)
+        .collect();
+    if stem.trim_matches('_').is_empty() {
+        stem = "customer".into();
+    }
+    let mut name = format!("{stem}.json");
+    let mut n = 1;
+    while !taken.insert(name.clone()) {
+        n += 1;
+        name = format!("{stem}-{n}.json");
+    }
+    name
+}
+
+async fn bulk_tag(State(db): State<Db>, JsonBody(payload): JsonBody<BulkTagDto>) -> Result<Json<serde_json::Value>, ApiError> {
+    let modified = db::bulk_tag(db, payload).await?;
+    Ok(Json(json!({ "modified": modified })))
+}
+
+/// Reports which status changes in the batch would be accepted, in order, without applying any
+async fn preflight_bulk_status(
+    State(db): State<Db>,
+    Extension(case): Extension<StatusCase>,
+    Query(query): Query<StatusQuery>,
+    JsonBody(payload): JsonBody<BulkStatusDto>,
+) -> Json<serde_json::Value> {
+    let checks = db::preflight_status(db, payload.as_updates(case), query.idempotent).await;
+    let mut results = Vec::with_capacity(checks.len());
+    for (item, check) in payload.updates.iter().zip(checks) {
+        results.push(match check {
+            Ok(()) => json!({ "id": item.id, "status": item.status, "legal": true }),
+            Err(e) => json!({ "id": item.id, "status": item.status, "legal": false, "error": error_report(e).await.1 }),
+        });
+    }
+    Json(json!({ "results": results }))
+}
+
+/// Serialized order and its ETag, shared by GET and HEAD so both agree
+fn order_representation(order: &OrderResponseDto) -> Result<(String, Vec<u8>), ApiError> {
+    let body = serde_json::to_vec(order)?;
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    Ok((format!("\"{:016x}\"", hasher.finish()), body))
+}
+
+async fn get_one(State(db): State<Db>, OrderId(id): OrderId) -> Result<impl IntoResponse, ApiError> {
     let order = db::get_order(db, id).await?;
-    Ok(Json(order))
+    let (etag, body) = order_representation(&order)?;
+    Ok(([(header::CONTENT_TYPE, "application/json".to_string()), (header::ETAG, etag)], body))
 }
 
-async fn list(State(db): State<Db>) -> Result<Json<Vec<OrderResponseDto>>, ApiError> {
-    Ok(Json(db::list_orders(db).await))
+async fn head_one(State(db): State<Db>, OrderId(id): OrderId) -> Result<impl IntoResponse, ApiError> {
+    let order = db::get_order(db, id).await?;
+    let (etag, body) = order_representation(&order)?;
+    Ok([(header::ETAG, etag), (header::CONTENT_LENGTH, body.len().to_string())])
+}
+
+/// Media type opting into the paginated `{ items, page }` list body instead of a bare array
+const LIST_ENVELOPE: &str = "application/vnd.orders.v2+json";
+
+/// The ETag follows the store version, so any write changes it. `Accept` picks the body: the paginated
+/// envelope, `text/csv` with the export's columns, or by default a bare JSON array.
+async fn list(
+    State(db): State<Db>,
+    headers: http::HeaderMap,
+    ValidatedQuery(query): ValidatedQuery<ListQuery>,
+) -> Result<Response, ApiError> {
+    if let Some(conflict) = query.conflict() {
+        return Err(ApiError::BadRequest(conflict));
+    }
+    // The version comes from the same read as the orders, so the ETag always matches the body
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if accept.contains(LIST_ENVELOPE) {
+        let page = db::list_orders_page(db, query).await;
+        let headers = [(header::ETAG, format!("\"v{}-page\"", page.version)), (header::CONTENT_TYPE, LIST_ENVELOPE.to_string())];
+        let body = serde_json::to_vec(&page)?;
+        return Ok((headers, body).into_response());
+    }
+    let (version, orders) = db::list_orders_at(db, query).await;
+    if accept.contains(CSV) {
+        let truncated = truncation_headers(&orders);
+        let csv = write_csv(orders.items.iter().map(CsvExportRow::from))?;
+        let headers = [(header::ETAG, format!("\"v{version}-csv\"")), (header::CONTENT_TYPE, CSV.to_string())];
+        return Ok((headers, truncated, csv).into_response());
+    }
+    Ok(([(header::ETAG, format!("\"v{version}\""))], orders).into_response())
+}
+
+/// Marks a cut-off result set in the headers, keeping the body a bare array
+impl<T: serde::Serialize> IntoResponse for Capped<T> {
+    fn into_response(self) -> Response {
+        (truncation_headers(&self), Json(self.items)).into_response()
+    }
+}
+
+/// `X-Truncated` and a `Warning` when the cap cut the result set short, nothing otherwise
+fn truncation_headers<T>(capped: &Capped<T>) -> http::HeaderMap {
+    let mut headers = http::HeaderMap::new();
+    if capped.truncated {
+        let warning = format!("299 - \"result truncated to {} items\"", capped.items.len());
+        headers.insert(TRUNCATED, HeaderValue::from_static("true"));
+        headers.insert(header::WARNING, HeaderValue::from_str(&warning).expect("warning is ASCII"));
+    }
+    headers
+}
+
+const TRUNCATED: &str = "x-truncated";
+
+/// Bare array by default; the list envelope on request, as a single page since search has no offset
+async fn search(State(db): State<Db>, headers: http::HeaderMap, ValidatedQuery(query): ValidatedQuery<SearchQuery>) -> Result<Response, ApiError> {
+    let limit = query.limit;
+    let orders = db::search_orders(db, query).await?;
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if !accept.contains(LIST_ENVELOPE) {
+        return Ok(Json(orders).into_response());
+    }
+    let page = OrderListDto::single_page(orders, limit);
+    Ok(([(header::CONTENT_TYPE, LIST_ENVELOPE)], serde_json::to_vec(&page)?).into_response())
+}
+
+/// `application/merge-patch+json` is accepted by `Json` thanks to its `+json` suffix
+async fn merge_patch(
+    State(db): State<Db>,
+    OrderId(id): OrderId,
+    PreferMinimal(minimal): PreferMinimal,
+    Query(query): Query<PatchQuery>,
+    Extension(mode): Extension<JsonMode>,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Response, ApiError> {
+    if mode.strict {
+        db::check_patch_fields(&patch)?;
+    }
+    let patch = match query.update_mask {
+        Some(mask) => db::apply_update_mask(patch, &mask)?,
+        None => patch,
+    };
+    let order = db::merge_patch_order(db, id, patch).await?;
+    Ok(written(order, minimal, false))
 }
 
 async fn update_status(
     State(db): State<Db>,
-    Path(id): Path<Uuid>,
-    Json(payload): Json<UpdateStatusDto>,
+    OrderId(id): OrderId,
+    PreferMinimal(minimal): PreferMinimal,
+    Extension(case): Extension<StatusCase>,
+    Query(query): Query<StatusQuery>,
+    JsonBody(payload): JsonBody<UpdateStatusDto>,
+) -> Result<Response, ApiError> {
+    let payload = payload.with_case(case);
+    let order = if query.idempotent {
+        db::ensure_status(db, id, payload).await?
+    } else {
+        db::update_status(db, id, payload).await?
+    };
+    Ok(written(order, minimal, false))
+}
+
+async fn mark_delivered(
+    State(db): State<Db>,
+    OrderId(id): OrderId,
+    payload: Option<JsonBody<DeliveredDto>>,
 ) -> Result<Json<OrderResponseDto>, ApiError> {
-    let order = db::update_status(db, id, payload).await?;
+    let payload = payload.map(|JsonBody(payload)| payload).unwrap_or_default();
+    let order = db::mark_delivered(db, id, payload).await?;
     Ok(Json(order))
 }
 
-async fn delete_one(State(db): State<Db>, Path(id): Path<Uuid>) -> Result<(), ApiError> {
+async fn freeze(State(db): State<Db>, OrderId(id): OrderId) -> Result<Json<OrderResponseDto>, ApiError> {
+    Ok(Json(db::set_frozen(db, id, true).await?))
+}
+
+async fn unfreeze(State(db): State<Db>, OrderId(id): OrderId) -> Result<Json<OrderResponseDto>, ApiError> {
+    Ok(Json(db::set_frozen(db, id, false).await?))
+}
+
+async fn undelete(State(db): State<Db>, OrderId(id): OrderId) -> Result<Json<OrderResponseDto>, ApiError> {
+    Ok(Json(db::undelete_order(db, id).await?))
+}
+
+async fn set_assignee(
+    State(db): State<Db>,
+    OrderId(id): OrderId,
+    JsonBody(payload): JsonBody<AssigneeDto>,
+) -> Result<Json<OrderResponseDto>, ApiError> {
+    let order = db::set_assignee(db, id, payload).await?;
+    Ok(Json(order))
+}
+
+async fn history(
+    State(db): State<Db>,
+    OrderId(id): OrderId,
+    ValidatedQuery(query): ValidatedQuery<HistoryQuery>,
+) -> Result<Json<Vec<StatusChangeDto>>, ApiError> {
+    Ok(Json(db::order_history(db, id, query).await?))
+}
+
+async fn diff(
+    State(db): State<Db>,
+    OrderId(id): OrderId,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<OrderDiffDto>, ApiError> {
+    Ok(Json(db::order_diff(db, id, query).await?))
+}
+
+async fn events(State(db): State<Db>, Query(query): Query<EventsQuery>) -> Result<Capped<EventRecord>, ApiError> {
+    db::events_since(db, query.since).await
+}
+
+async fn event_snapshot(State(db): State<Db>) -> Result<Json<EventSnapshot>, ApiError> {
+    Ok(Json(db::event_snapshot(db).await?))
+}
+
+/// Replaces the orders with a replay of the event log, if the replay matches them
+async fn rebuild(State(db): State<Db>) -> Result<Json<serde_json::Value>, ApiError> {
+    let orders = db::rebuild_from_events(db).await?;
+    Ok(Json(json!({ "orders": orders })))
+}
+
+/// Identifies the service to anyone checking it is up
+async fn root() -> Json<serde_json::Value> {
+    Json(json!({ "service": "orders", "version": env!("CARGO_PKG_VERSION"), "docs": "/schema" }))
+}
+
+type SchemaFn = fn() -> schemars::Schema;
+
+/// JSON Schemas of the request and response bodies, by the name served under `/schema/{dto}`
+const SCHEMAS: &[(&str, SchemaFn)] = &[
+    ("create-order", || schemars::schema_for!(CreateOrderDto)),
+    ("update-status", || schemars::schema_for!(UpdateStatusDto)),
+    ("order", || schemars::schema_for!(OrderResponseDto)),
+];
+
+async fn schema_index() -> Json<Vec<&'static str>> {
+    Json(SCHEMAS.iter().map(|(name, _)| *name).collect())
+}
+
+async fn schema(Path(name): Path<String>) -> Result<Json<schemars::Schema>, ApiError> {
+    let (_, generate) = SCHEMAS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .ok_or(ApiError::NotFound { resource: "schema" })?;
+    Ok(Json(generate()))
+}
+
+async fn transitions(State(db): State<Db>, OrderId(id): OrderId) -> Result<Json<&'static [&'static str]>, ApiError> {
+    Ok(Json(db::order_transitions(db, id).await?))
+}
+
+async fn delete_one(State(db): State<Db>, OrderId(id): OrderId) -> Result<(), ApiError> {
     db::delete_order(db, id).await?;
     Ok(())
 }
+
+async fn store_stats(State(db): State<Db>) -> Json<StoreStatsDto> {
+    Json(db::store_stats(db).await)
+}
+
+async fn order_ages(State(db): State<Db>) -> Json<BTreeMap<String, AgeStatsDto>> {
+    Json(db::order_stats(db).await)
+}
+
+/// Prometheus text exposition of the per-status order ages
+fn render_prometheus(stats: &BTreeMap<String, AgeStatsDto>) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP orders_total Orders currently in each status\n# TYPE orders_total gauge\n");
+    for (status, s) in stats {
+        let _ = writeln!(out, "orders_total{{status=\"{status}\"}} {}", s.count);
+    }
+    age_gauge(&mut out, "order_age_seconds_min", "Youngest order in each status", stats, |s| s.min_secs);
+    age_gauge(&mut out, "order_age_seconds_avg", "Average time orders have sat in each status", stats, |s| s.avg_secs);
+    age_gauge(&mut out, "order_age_seconds_max", "Oldest order in each status", stats, |s| s.max_secs);
+    out
+}
+
+fn age_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    stats: &BTreeMap<String, AgeStatsDto>,
+    value: impl Fn(&AgeStatsDto) -> Option<f64>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+    // Empty buckets have no age, so their series is left out
+    for (status, age) in stats.iter().filter_map(|(status, s)| Some((status, value(s)?))) {
+        let _ = writeln!(out, "{name}{{status=\"{status}\"}} {age}");
+    }
+}
+
+async fn metrics(State(db): State<Db>) -> impl IntoResponse {
+    let stats = db::order_stats(db.clone()).await;
+    let mut body = render_prometheus(&stats);
+    let _ = writeln!(
+        body,
+        "# HELP http_requests_total Requests handled, including those left out of the logs\n# TYPE http_requests_total counter\nhttp_requests_total {}",
+        logging::requests_total()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP dedup_cache_entries Recent creates remembered for duplicate detection\n# TYPE dedup_cache_entries gauge\ndedup_cache_entries {}",
+        db::store_stats(db).await.recent_creates
+    );
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+/// Streams one `Order` per line, reading each order from the store as it is sent
+async fn backup_jsonl(State(db): State<Db>, ValidatedQuery(range): ValidatedQuery<CreatedRange>) -> Result<Response, ApiError> {
+    let range = checked(range)?;
+    let ids = db::order_ids(db.clone()).await;
+    let lines = stream::iter(ids)
+        .filter_map(move |id| {
+            let order = db::export_order(db.clone(), id);
+            async move { order.await.filter(|order| range.contains(order.created_at)) }
+        })
+        .map(|order| {
+            let mut line = serde_json::to_vec(&order)?;
+            line.push(b'\n');
+            Ok::<_, serde_json::Error>(Bytes::from(line))
+        });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response())
+}
+
+/// Longest NDJSON line accepted, so a missing newline cannot grow the buffer without bound
+const MAX_NDJSON_LINE: usize = 64 * 1024;
+
+/// Splits a body into numbered NDJSON lines as it arrives, skipping blank ones.
+/// Only the current partial line is buffered; a read error or an oversized line ends the stream.
+fn ndjson_lines(body: Body) -> impl Stream<Item = (usize, Result<Vec<u8>, ApiError>)> {
+    let state = (body.into_data_stream(), Vec::new(), 0, false);
+    stream::unfold(state, |(mut chunks, mut buffer, mut line_no, mut done)| async move {
+        loop {
+            if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                line_no += 1;
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Some(((line_no, Ok(line)), (chunks, buffer, line_no, done)));
+            }
+            if done {
+                return None;
+            }
+            if buffer.len() > MAX_NDJSON_LINE {
+                let error = ApiError::BadRequest(format!("line {}: longer than {MAX_NDJSON_LINE} bytes", line_no + 1));
+                return Some(((line_no + 1, Err(error)), (chunks, Vec::new(), line_no, true)));
+            }
+            match chunks.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    let error = ApiError::BadRequest(e.to_string());
+                    return Some(((line_no + 1, Err(error)), (chunks, Vec::new(), line_no, true)));
+                }
+                None => {
+                    // Last line may not be terminated by a newline
+                    buffer.push(b'\n');
+                    done = true;
+                }
+            }
+        }
+    })
+}
+
+/// Valid orders a best-effort restore holds before inserting them under one write lock
+const RESTORE_BATCH: usize = 256;
+
+/// Most orders an atomic restore accepts, as it holds all of them in memory until the last line
+const MAX_ATOMIC_RESTORE: usize = 10_000;
+
+/// Accepts JSON Lines, reading the body as it arrives.
+/// `atomic` checks every line before restoring any, so it holds every order of the payload in memory
+/// until the end and refuses payloads over `MAX_ATOMIC_RESTORE` orders with 413; `best_effort` inserts
+/// the valid ones in batches of `RESTORE_BATCH` and reports the rest. Ids that replaced an order
+/// already in the store are listed as `overwritten`.
+async fn restore_jsonl(
+    State(db): State<Db>,
+    Query(query): Query<RestoreQuery>,
+    body: Body,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut lines = std::pin::pin!(ndjson_lines(body));
+    let mut orders = Vec::new();
+    let mut restored = 0;
+    let mut overwritten = Vec::new();
+    let mut skipped = Vec::new();
+    while let Some((line_no, line)) = lines.next().await {
+        let checked = line.and_then(|line| {
+            let order: Order = serde_json::from_slice(&line)
+                .map_err(|e| ApiError::BadRequest(format!("line {line_no}: {e}")))?;
+            db::check_restorable(&order)?;
+            Ok(order)
+        });
+        match checked {
+            Ok(order) => orders.push(order),
+            Err(e) if query.mode == RestoreMode::BestEffort => {
+                let (status, error) = error_report(e).await;
+                skipped.push(json!({ "line": line_no, "status": status, "error": error }));
+            }
+            Err(e) => return Err(e),
+        }
+        match query.mode {
+            RestoreMode::Atomic if orders.len() > MAX_ATOMIC_RESTORE => {
+                let message = format!("an atomic restore takes at most {MAX_ATOMIC_RESTORE} orders; split the backup or use mode=best_effort");
+                return Err(ApiError::PayloadTooLarge(message));
+            }
+            RestoreMode::BestEffort if orders.len() >= RESTORE_BATCH => {
+                restored += orders.len();
+                overwritten.extend(db::restore_orders(db.clone(), std::mem::take(&mut orders)).await);
+            }
+            _ => {}
+        }
+    }
+    restored += orders.len();
+    overwritten.extend(db::restore_orders(db, orders).await);
+    match query.mode {
+        RestoreMode::Atomic => Ok(Json(json!({ "restored": restored, "overwritten": overwritten }))),
+        RestoreMode::BestEffort => Ok(Json(json!({ "restored": restored, "overwritten": overwritten, "skipped": skipped }))),
+    }
+}
+
+/// Status and JSON body an error would be sent with, for reporting it inside a larger response
+async fn error_report(e: ApiError) -> (u16, serde_json::Value) {
+    let response = e.into_response();
+    let status = response.status().as_u16();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap_or_default();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+/// Creates one order per NDJSON line, streaming back one result line per input line as it goes
+async fn bulk_create(
+    State(db): State<Db>,
+    Extension(mode): Extension<JsonMode>,
+    headers: http::HeaderMap,
+    body: Body,
+) -> Result<Response, ApiError> {
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-ndjson"));
+    if !is_ndjson {
+        return Err(ApiError::BadRequest("expected Content-Type: application/x-ndjson".into()));
+    }
+
+    // The body is polled after the middleware has returned, so each line is rendered with the
+    // request's language, error format, length limits and span captured here
+    let lang = i18n::current();
+    let format = errors::current_format();
+    let limits = order_dtos::current_limits();
+    let span = tracing::Span::current();
+    let results = ndjson_lines(body).then(move |(line_no, line)| {
+        let db = db.clone();
+        let line = async move {
+            let created = match line {
+                Ok(line) => {
+                    let parsed = serde_json::from_slice::<CreateOrderDto>(&line).map_err(|e| ApiError::BadRequest(e.to_string()));
+                    match parsed.and_then(|dto| mode.check::<CreateOrderDto>(&line).map(|()| dto)) {
+                        Ok(dto) => db::create_order(db, dto).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            let result = match created {
+                Ok(order) => json!({ "line": line_no, "status": 200, "order": order }),
+                Err(e) => {
+                    let (status, error) = error_report(e).await;
+                    json!({ "line": line_no, "status": status, "error": error })
+                }
+            };
+            let mut out = serde_json::to_vec(&result)?;
+            out.push(b'\n');
+            Ok::<_, serde_json::Error>(Bytes::from(out))
+        };
+        i18n::with_lang(lang, errors::with_format(format, order_dtos::with_limits(limits, line))).instrument(span.clone())
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(results)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use crate::db::Store;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+    use chrono::Utc;
+
+    fn create_test_db() -> Db {
+        Arc::new(RwLock::new(Store::default()))
+    }
+
+    async fn send(db: Db, request: Request<Body>) -> (StatusCode, Bytes) {
+        let response = app(db, &Config::default()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_backup_restore_round_trip() {
+        let db = create_test_db();
+        for customer in ["Alice", "Bob", "Carol"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+
+        let request = Request::get("/admin/backup.jsonl").body(Body::empty()).unwrap();
+        let (status, backup) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(backup.iter().filter(|b| **b == b'\n').count(), 3);
+
+        db.write().await.orders.clear();
+
+        let request = Request::post("/admin/restore")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from(backup.clone()))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["restored"], 3);
+        assert_eq!(json["overwritten"], json!([]));
+        assert_eq!(db.read().await.orders.len(), 3);
+
+        // Restoring over live orders replaces them and says which
+        let first = backup.split(|b| *b == b'\n').next().unwrap().to_vec();
+        let id = serde_json::from_slice::<serde_json::Value>(&first).unwrap()["id"].clone();
+        let request = Request::post("/admin/restore")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from(first))
+            .unwrap();
+        let (_, body) = send(db.clone(), request).await;
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["restored"], 1);
+        assert_eq!(json["overwritten"], json!([id]));
+        assert_eq!(db.read().await.orders.len(), 3);
+    }
+
+    async fn create_with_language(lang: &str) -> serde_json::Value {
+        let request = Request::post("/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT_LANGUAGE, lang)
+            .body(Body::from(r#"{"customer":"","items":[]}"#))
+            .unwrap();
+        let (status, body) = send(create_test_db(), request).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_validation_messages_in_spanish() {
+        let json = create_with_language("es-ES,es;q=0.9").await;
+        assert_eq!(json["details"]["customer"][0], "el nombre del cliente no puede estar vacío");
+        assert_eq!(json["details"]["items"][0], "se requiere al menos un artículo");
+    }
+
+    #[tokio::test]
+    async fn test_validation_messages_in_english() {
+        let json = create_with_language("en-US").await;
+        assert_eq!(json["details"]["customer"][0], "customer name must not be empty");
+        assert_eq!(json["details"]["items"][0], "at least one item required");
+    }
+
+    fn create_request(prefer: Option<&str>) -> Request<Body> {
+        let mut request = Request::post("/orders").header(header::CONTENT_TYPE, "application/json");
+        if let Some(prefer) = prefer {
+            request = request.header("prefer", prefer);
+        }
+        request.body(Body::from(r#"{"customer":"Alice","items":["Book"]}"#)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_prefer_minimal() {
+        let db = create_test_db();
+        let response = app(db.clone(), &Config::default()).oneshot(create_request(Some("return=minimal"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[PREFERENCE_APPLIED], "return=minimal");
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+
+        let id = db.read().await.orders.keys().next().copied().unwrap();
+        assert_eq!(location, format!("/orders/{id}"));
+    }
+
+    #[tokio::test]
+    async fn test_create_default_representation() {
+        let response = app(create_test_db(), &Config::default()).oneshot(create_request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(PREFERENCE_APPLIED));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["customer"], "Alice");
+
+        // Other preferences leave the representation alone
+        let response = app(create_test_db(), &Config::default()).oneshot(create_request(Some("return=representation"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_create_ignores_status() {
+        let request = Request::post("/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"Alice","items":["Book"],"status":"shipped"}"#))
+            .unwrap();
+        let (status, body) = send(create_test_db(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "pending");
+    }
+
+    #[tokio::test]
+    async fn test_create_instant_order() {
+        let db = create_test_db();
+        let request = Request::post("/orders/instant")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"Alice","items":["Book"]}"#))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "shipped");
+
+        let request = Request::post("/orders/instant")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"Alice","items":["Book"],"status":"delivered"}"#))
+            .unwrap();
+        let (status, _) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(db.read().await.orders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_head_existing_order() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let uri = format!("/orders/{}", order.id);
+
+        let request = Request::get(&uri).body(Body::empty()).unwrap();
+        let get_response = app(db.clone(), &Config::default()).oneshot(request).await.unwrap();
+        let get_etag = get_response.headers()[header::ETAG].clone();
+        let get_body = axum::body::to_bytes(get_response.into_body(), usize::MAX).await.unwrap();
+
+        let request = Request::head(&uri).body(Body::empty()).unwrap();
+        let response = app(db, &Config::default()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], get_etag);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], get_body.len().to_string());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_head_missing_order() {
+        let request = Request::head(format!("/orders/{}", Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send(create_test_db(), request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_history_endpoint() {
+        let db = create_test_db();
+        let request = Request::post("/orders/instant")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"Alice","items":["Book"]}"#))
+            .unwrap();
+        let (_, body) = send(db.clone(), request).await;
+        let order: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let uri = format!("/orders/{}/history?limit=1&after=2000-01-01T00:00:00Z&to=shipped", order["id"].as_str().unwrap());
+        let (status, body) = send(db.clone(), Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let history: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(history.as_array().unwrap().len(), 1);
+        assert_eq!(history[0]["from"], "pending");
+
+        let uri = format!("/orders/{}/history", Uuid::new_v4());
+        let (status, _) = send(db, Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_order_id_is_structured_400() {
+        let db = create_test_db();
+        for request in [
+            Request::get("/orders/not-a-uuid").body(Body::empty()).unwrap(),
+            Request::delete("/orders/not-a-uuid").body(Body::empty()).unwrap(),
+            Request::put("/orders/not-a-uuid/status")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"status":"shipped"}"#))
+                .unwrap(),
+        ] {
+            let (status, body) = send(db.clone(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["code"], "bad_request");
+            assert_eq!(json["message"], "Invalid input: invalid order id");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_rejects_conflicting_params() {
+        let db = create_test_db();
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        for (uri, conflict) in [("/orders?status=pending&sort=-status", "sort=status"), ("/orders?offset=10", "offset requires limit")] {
+            let (status, body) = send(db.clone(), get(uri)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["code"], "bad_request");
+            assert!(json["message"].as_str().unwrap().contains(conflict), "{json}");
+        }
+
+        let (status, _) = send(db, get("/orders?status=pending&sort=-customer&limit=10&offset=10")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_list_envelope() {
+        let db = create_test_db();
+        let envelope = |uri: &str| Request::get(uri).header(header::ACCEPT, LIST_ENVELOPE).body(Body::empty()).unwrap();
+
+        let (status, body) = send(db.clone(), envelope("/orders")).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["items"], json!([]));
+        assert_eq!(json["page"], json!({ "limit": null, "next_cursor": null, "total": 0 }));
+
+        for customer in ["Alice", "Bob"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+        let (_, body) = send(db.clone(), envelope("/orders?limit=1&sort=customer")).await;
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["items"][0]["customer"], "Alice");
+        assert_eq!(json["page"], json!({ "limit": 1, "next_cursor": "1", "total": 2 }));
+
+        // Without the media type the bare array is kept
+        let (_, body) = send(db, Request::get("/orders").body(Body::empty()).unwrap()).await;
+        assert!(serde_json::from_slice::<serde_json::Value>(&body).unwrap().is_array());
+    }
+
+    #[tokio::test]
+    async fn test_search_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Jane Doe".to_string(),
+            items: vec!["Widget".to_string()],
+        };
+        db::create_order(db.clone(), dto).await.unwrap();
+
+        let (status, body) = send(db.clone(), Request::get("/orders/search?q=widget").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["customer"], "Jane Doe");
+
+        let (status, _) = send(db, Request::get("/orders/search?q=").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_configured_customer_limit_applies_to_create_and_patch() {
+        let db = create_test_db();
+        let config = Config { length_limits: order_dtos::LengthLimits { customer: 5, ..Default::default() }, ..Config::default() };
+        let create = |customer: &str| {
+            let body = json!({ "customer": customer, "items": ["Book"] }).to_string();
+            Request::post("/orders").header(header::CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap()
+        };
+        let response = app(db.clone(), &config).oneshot(create("Alice")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_str().unwrap().to_string();
+
+        let response = app(db.clone(), &config).oneshot(create("Alison")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["details"]["customer"], json!(["customer name is too long"]));
+
+        let patch = Request::patch(format!("/orders/{id}"))
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .body(Body::from(json!({ "customer": "Alison" }).to_string()))
+            .unwrap();
+        let response = app(db.clone(), &config).oneshot(patch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        assert_eq!(app(db, &Config::default()).oneshot(create("Alison")).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_configured_item_limit_applies_to_create_and_patch() {
+        let db = create_test_db();
+        let config = Config { length_limits: order_dtos::LengthLimits { item: 8, ..Default::default() }, ..Config::default() };
+        let create = |item: &str| {
+            let body = json!({ "customer": "Alice", "items": [item] }).to_string();
+            Request::post("/orders").header(header::CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap()
+        };
+        let response = app(db.clone(), &config).oneshot(create("Notebook")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_str().unwrap().to_string();
+
+        let response = app(db.clone(), &config).oneshot(create("Notebooks")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["details"]["items"], json!(["item 0 is longer than 8 characters"]));
+
+        let patch = Request::patch(format!("/orders/{id}"))
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .body(Body::from(json!({ "items": ["Notebooks"] }).to_string()))
+            .unwrap();
+        let response = app(db.clone(), &config).oneshot(patch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // The default limit still takes the longer name
+        assert_eq!(app(db, &Config::default()).oneshot(create("Notebooks")).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_search_envelope() {
+        let db = create_test_db();
+        let envelope = |uri: &str| Request::get(uri).header(header::ACCEPT, LIST_ENVELOPE).body(Body::empty()).unwrap();
+
+        let response = app(db.clone(), &Config::default()).oneshot(envelope("/orders/search?q=widget")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], LIST_ENVELOPE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, json!({ "items": [], "page": { "limit": null, "next_cursor": null, "total": 0 } }));
+
+        let dto = CreateOrderDto {
+            customer: "Jane Doe".to_string(),
+            items: vec!["Widget".to_string()],
+        };
+        db::create_order(db.clone(), dto).await.unwrap();
+        let (_, body) = send(db, envelope("/orders/search?q=widget&limit=5")).await;
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["items"][0]["customer"], "Jane Doe");
+        assert_eq!(json["page"], json!({ "limit": 5, "next_cursor": null, "total": 1 }));
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_set_status_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let put = |body: &'static str| {
+            Request::put(format!("/orders/{}/status", order.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let (status, _) = send(db.clone(), put(r#"{"expected":"pending","status":"shipped"}"#)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(db, put(r#"{"expected":"pending","status":"cancelled"}"#)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "status_mismatch");
+        assert_eq!(json["details"], json!({ "expected": "pending", "actual": "shipped" }));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_handler_returns_clean_500() {
+        async fn boom() -> &'static str {
+            panic!("database password is hunter2")
+        }
+        let app = with_middleware(Router::new().route("/boom", get(boom)), &Config::default());
+
+        let response = app.oneshot(Request::get("/boom").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().contains_key(REQUEST_ID));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "internal");
+        assert!(!String::from_utf8_lossy(&body).contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_echoed() {
+        let db = create_test_db();
+        let request = Request::get("/orders").header(REQUEST_ID, "abc-123").body(Body::empty()).unwrap();
+        let response = app(db, &Config::default()).oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_events_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        db::update_status(db.clone(), order.id, UpdateStatusDto { status: "shipped".to_string(), expected: None }).await.unwrap();
+        db::delete_order(db.clone(), order.id).await.unwrap();
+
+        let (status, body) = send(db.clone(), Request::get("/events").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let summary: Vec<_> = events.iter().map(|e| (e["seq"].as_u64().unwrap(), e["type"].as_str().unwrap())).collect();
+        assert_eq!(summary, [(1, "OrderCreated"), (2, "StatusChanged"), (3, "OrderDeleted")]);
+        assert!(events.iter().all(|e| e["order_id"] == order.id.to_string()));
+        assert_eq!(events[1]["from"], "pending");
+        assert_eq!(events[1]["to"], "shipped");
+
+        let (_, body) = send(db, Request::get("/events?since=2").body(Body::empty()).unwrap()).await;
+        let events: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["type"], "OrderDeleted");
+    }
+
+    #[tokio::test]
+    async fn test_unpaginated_results_are_capped() {
+        let mut store = Store::default();
+        store.max_results = Some(2);
+        let db = Arc::new(RwLock::new(store));
+        for customer in ["Alice", "Bob", "Carol"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+
+        for uri in ["/orders", "/events"] {
+            let response = app(db.clone(), &Config::default())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.headers()[TRUNCATED], "true", "{uri}");
+            assert_eq!(response.headers()[header::WARNING], "299 - \"result truncated to 2 items\"");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let items: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(items.len(), 2, "{uri}");
+        }
+
+        // An explicit limit is pagination, and a set at the cap is complete
+        for uri in ["/orders?limit=3", "/events?since=1"] {
+            let response = app(db.clone(), &Config::default())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(!response.headers().contains_key(TRUNCATED), "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_endpoint() {
+        let db = create_test_db();
+        for customer in ["Alice", "Bob"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+
+        let (status, body) = send(db.clone(), Request::post("/admin/rebuild").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "orders": 2 }));
+
+        // Freezes are not logged, so the replay no longer matches
+        db.write().await.orders.values_mut().for_each(|order| order.frozen = true);
+        let (status, _) = send(db, Request::post("/admin/rebuild").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_templates() {
+        let db = create_test_db();
+        let save = |body: &'static str| {
+            Request::post("/templates")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let (status, _) = send(db.clone(), save(r#"{"name":"monthly-coffee","customer":"Jane","items":["Beans","Filters"]}"#)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(db.clone(), save(r#"{"name":"no spaces","customer":"Jane","items":["Beans"]}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = send(db.clone(), Request::get("/templates").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let templates: Vec<OrderTemplateDto> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["monthly-coffee"]);
+
+        let instantiate = |name: &str| Request::post(format!("/templates/{name}/instantiate")).body(Body::empty()).unwrap();
+        for _ in 0..2 {
+            let (status, body) = send(db.clone(), instantiate("monthly-coffee")).await;
+            assert_eq!(status, StatusCode::OK);
+            let order: OrderResponseDto = serde_json::from_slice(&body).unwrap();
+            assert_eq!(order.customer, "Jane");
+            assert_eq!(order.items, ["Beans", "Filters"]);
+            assert_eq!(order.status, "pending");
+        }
+        assert_eq!(db.read().await.orders.len(), 2);
+
+        let (status, body) = send(db, instantiate("weekly-tea")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"], "template_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_root_identifies_the_service() {
+        let (status, body) = send(create_test_db(), Request::get("/").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["service"], "orders");
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["docs"], "/schema");
+    }
+
+    #[tokio::test]
+    async fn test_schema_endpoints() {
+        let (status, body) = send(create_test_db(), Request::get("/schema").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let names: Vec<String> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(names, ["create-order", "update-status", "order"]);
+
+        let (status, body) = send(create_test_db(), Request::get("/schema/create-order").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let schema: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(schema["properties"]["customer"]["type"], "string");
+        assert_eq!(schema["properties"]["items"]["type"], "array");
+        assert_eq!(schema["required"], json!(["customer", "items"]));
+
+        let (status, body) = send(create_test_db(), Request::get("/schema/nope").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "schema_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_diff_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        db::update_status(db.clone(), order.id, UpdateStatusDto { status: "shipped".to_string(), expected: None }).await.unwrap();
+
+        let uri = format!("/orders/{}/diff?from=0&to=1", order.id);
+        let (status, body) = send(db.clone(), Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["changes"], json!({ "status": { "from": "pending", "to": "shipped" } }));
+
+        let uri = format!("/orders/{}/diff?from=0&to=5", order.id);
+        let (status, body) = send(db, Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "version_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_transitions_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let uri = format!("/orders/{}/transitions", order.id);
+        let (status, body) = send(db.clone(), Request::get(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!(["shipped", "cancelled"]));
+
+        for next in ["shipped", "delivered"] {
+            db::update_status(db.clone(), order.id, UpdateStatusDto { status: next.to_string(), expected: None }).await.unwrap();
+        }
+        let (_, body) = send(db.clone(), Request::get(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!([]));
+
+        let uri = format!("/orders/{}/transitions", Uuid::new_v4());
+        let (status, _) = send(db, Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cache_headers_and_list_etag() {
+        let db = create_test_db();
+        let config = Config {
+            cache_max_age: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let app = app(db.clone(), &config);
+        let get = |uri: String| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(get("/orders".into())).await.unwrap();
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "max-age=30");
+        assert_eq!(response.headers()[header::VARY], "accept");
+        let first_etag = response.headers()[header::ETAG].clone();
+
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let response = app.clone().oneshot(get("/orders".into())).await.unwrap();
+        assert_ne!(response.headers()[header::ETAG], first_etag);
+
+        let response = app.clone().oneshot(get(format!("/orders/{}", order.id))).await.unwrap();
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "max-age=30");
+        assert!(response.headers().contains_key(header::ETAG));
+
+        // Errors and writes are never cacheable
+        let response = app.clone().oneshot(get(format!("/orders/{}", Uuid::new_v4()))).await.unwrap();
+        assert!(!response.headers().contains_key(header::CACHE_CONTROL));
+        let response = app.clone().oneshot(Request::delete(format!("/orders/{}", order.id)).body(Body::empty()).unwrap()).await.unwrap();
+        assert!(!response.headers().contains_key(header::CACHE_CONTROL));
+
+        // Nor are reads outside the order routes
+        for uri in ["/metrics", "/admin/stats", "/admin/backup.jsonl", "/events", "/orders/export.csv"] {
+            let response = app.clone().oneshot(get(uri.into())).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            assert!(!response.headers().contains_key(header::CACHE_CONTROL), "{uri}");
+            assert!(!response.headers().contains_key(header::VARY), "{uri}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_list_etag_matches_body_under_concurrent_writes() {
+        let db = create_test_db();
+        let writer = tokio::spawn({
+            let db = db.clone();
+            async move {
+                for n in 0..200 {
+                    let dto = CreateOrderDto {
+                        customer: format!("customer {n}"),
+                        items: vec!["Book".to_string()],
+                    };
+                    db::create_order(db.clone(), dto).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+        let app = app(db, &Config::default());
+        while !writer.is_finished() {
+            let response = app.clone().oneshot(Request::get("/orders").body(Body::empty()).unwrap()).await.unwrap();
+            let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let count = serde_json::from_slice::<Vec<serde_json::Value>>(&body).unwrap().len();
+            // Each create is one write, so the version is the number of orders the body should hold
+            assert_eq!(etag, format!("\"v{count}\""));
+        }
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice, Jr.".to_string(),
+            items: vec!["Book".to_string(), "Pen".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let response = app(db, &Config::default())
+            .oneshot(Request::get("/orders/export.csv").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("id,customer,items,status,assignee,created_at"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with(&format!("{},\"Alice, Jr.\",Book|Pen,pending,,", order.id)), "{row}");
+        assert_eq!(lines.next(), None);
+    }
+
+    #[tokio::test]
+    async fn test_empty_csv_exports_keep_the_header() {
+        let db = create_test_db();
+        let header_line = format!("{}\n", CsvExportRow::HEADER.join(","));
+        let csv_list = || Request::get("/orders").header(header::ACCEPT, CSV).body(Body::empty()).unwrap();
+        for request in [Request::get("/orders/export.csv").body(Body::empty()).unwrap(), csv_list()] {
+            let (status, body) = send(db.clone(), request).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, header_line.as_bytes());
+        }
+
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        db::create_order(db.clone(), dto).await.unwrap();
+        let (_, body) = send(db, csv_list()).await;
+        assert!(body.starts_with(header_line.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_export_csv_byte_ranges() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        db::create_order(db.clone(), dto).await.unwrap();
+        let export = |range: Option<&str>, if_range: Option<&str>| {
+            let mut request = Request::get("/orders/export.csv");
+            if let Some(range) = range {
+                request = request.header(header::RANGE, range);
+            }
+            if let Some(if_range) = if_range {
+                request = request.header(header::IF_RANGE, if_range);
+            }
+            app(db.clone(), &Config::default()).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = export(None, None).await.unwrap();
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        let full = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        for (range, slice) in [("bytes=3-9", &full[3..=9]), ("bytes=10-", &full[10..]), ("bytes=-5", &full[full.len() - 5..])] {
+            let response = export(Some(range), Some(&etag)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT, "{range}");
+            assert_eq!(response.headers()[header::ETAG], etag.as_str());
+            let content_range = response.headers()[header::CONTENT_RANGE].to_str().unwrap().to_string();
+            assert!(content_range.ends_with(&format!("/{}", full.len())), "{content_range}");
+            assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), slice, "{range}");
+        }
+        let response = export(Some("bytes=3-9"), None).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_RANGE], format!("bytes 3-9/{}", full.len()));
+
+        // Unknown units, multiple and malformed ranges, and a stale If-Range all get the full body
+        for (range, if_range) in [("items=0-1", None), ("bytes=0-1,5-6", None), ("bytes=9-3", None), ("bytes=3-9", Some("\"v0-export\""))] {
+            let response = export(Some(range), if_range).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{range}");
+            assert!(response.headers().get(header::CONTENT_RANGE).is_none(), "{range}");
+            assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), full, "{range}");
+        }
+
+        let past_end = format!("bytes={}-", full.len());
+        for range in [past_end.as_str(), "bytes=-0"] {
+            let response = export(Some(range), None).await.unwrap();
+            assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE, "{range}");
+            assert_eq!(response.headers()[header::CONTENT_RANGE], format!("bytes */{}", full.len()));
+        }
+
+        db::create_order(db.clone(), CreateOrderDto { customer: "Bob".to_string(), items: vec!["Pen".to_string()] }).await.unwrap();
+        let response = export(Some("bytes=3-9"), Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_exports_scoped_to_a_month() {
+        let db = create_test_db();
+        for (customer, created_at) in [
+            ("January", "2024-01-31T23:59:59Z"),
+            ("February first", "2024-02-01T00:00:00Z"),
+            ("February", "2024-02-15T12:00:00Z"),
+            ("March", "2024-03-01T00:00:00Z"),
+        ] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            let id = db::create_order(db.clone(), dto).await.unwrap().id;
+            db.write().await.orders.get_mut(&id).unwrap().created_at = created_at.parse().unwrap();
+        }
+        let february = "created_after=2024-02-01T00:00:00Z&created_before=2024-03-01T00:00:00Z";
+
+        let request = Request::get(format!("/orders/export.csv?{february}")).body(Body::empty()).unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let customers: Vec<String> = csv::Reader::from_reader(&body[..])
+            .records()
+            .map(|record| record.unwrap()[1].to_string())
+            .collect();
+        assert_eq!(customers, ["February first", "February"]);
+
+        let request = Request::get(format!("/admin/backup.jsonl?{february}")).body(Body::empty()).unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut customers: Vec<String> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<Order>(line).unwrap().customer)
+            .collect();
+        customers.sort();
+        assert_eq!(customers, ["February", "February first"]);
+
+        // The list takes the same range
+        let request = Request::get(format!("/orders?{february}&limit=10")).body(Body::empty()).unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<Vec<OrderResponseDto>>(&body).unwrap().len(), 2);
+
+        let inverted = "created_after=2024-03-01T00:00:00Z&created_before=2024-02-01T00:00:00Z";
+        let request = Request::get(format!("/orders/export.csv?{inverted}")).body(Body::empty()).unwrap();
+        let (status, _) = send(db, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_flat_error_format() {
+        let config = Config { error_format: errors::ErrorFormat::Flat, ..Default::default() };
+        let request = Request::post("/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"","items":[]}"#))
+            .unwrap();
+        let response = app(create_test_db(), &config).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let fields: Vec<&str> = json["details"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+        assert_eq!(fields, ["customer", "items"]);
+    }
+
+    #[tokio::test]
+    async fn test_status_case_modes() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let shipped = || {
+            Request::put(format!("/orders/{}/status", order.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"status":"Shipped","expected":"PENDING"}"#))
+                .unwrap()
+        };
+
+        let (status, _) = send(db.clone(), shipped()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(db::get_order(db.clone(), order.id).await.unwrap().status, "pending");
+
+        let lenient = Config { status_case: StatusCase::Lenient, ..Default::default() };
+        let response = app(db.clone(), &lenient).oneshot(shipped()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_order(db, order.id).await.unwrap().status, "shipped");
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_status_flag() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let set = |query: &str, status: &str| {
+            Request::put(format!("/orders/{}/status{query}", order.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "status": status }).to_string()))
+                .unwrap()
+        };
+
+        assert_eq!(send(db.clone(), set("", "pending")).await.0, StatusCode::CONFLICT);
+        assert_eq!(send(db.clone(), set("?idempotent=true", "pending")).await.0, StatusCode::OK);
+        assert_eq!(send(db.clone(), set("?idempotent=true", "delivered")).await.0, StatusCode::CONFLICT);
+        assert_eq!(send(db.clone(), set("?idempotent=true", "shipped")).await.0, StatusCode::OK);
+        assert_eq!(db::get_order(db, order.id).await.unwrap().status, "shipped");
+    }
+
+    #[tokio::test]
+    async fn test_strict_json_rejects_unknown_fields() {
+        let db = create_test_db();
+        let body = r#"{"customer":"Alice","items":["Book"],"custmer":"Alicia"}"#;
+        let create = || {
+            Request::post("/orders")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let (status, _) = send(db.clone(), create()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let strict = Config { strict_json: true, ..Default::default() };
+        let response = app(db.clone(), &strict).oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["message"], "Invalid input: unknown field `custmer`");
+
+        // Nested fields and patches are checked too
+        let order = db.read().await.orders.keys().next().copied().unwrap();
+        let requests = [
+            ("/orders/bulk-tag".to_string(), Method::POST, r#"{"filter":{"stauts":"pending"},"add":["x"]}"#, "filter.stauts"),
+            (format!("/orders/{order}"), Method::PATCH, r#"{"asignee":"bob"}"#, "asignee"),
+            ("/orders/instant".to_string(), Method::POST, r#"{"customer":"Bob","items":["Pen"],"stauts":"shipped"}"#, "stauts"),
+        ];
+        for (uri, method, body, field) in requests {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let response = app(db.clone(), &strict).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["message"], format!("Invalid input: unknown field `{field}`"));
+        }
+
+        // Known fields still go through
+        let request = Request::put(format!("/orders/{order}/status"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"status":"shipped","expected":"pending"}"#))
+            .unwrap();
+        assert_eq!(app(db, &strict).oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_export_zip() {
+        let db = create_test_db();
+        let mut alice_ids = Vec::new();
+        for (customer, item) in [("Alice", "Book"), ("../Bob/", "Pen"), ("Alice", "Lamp"), ("../Bob?", "Mug")] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec![item.to_string()],
+            };
+            let order = db::create_order(db.clone(), dto).await.unwrap();
+            if customer == "Alice" {
+                alice_ids.push(order.id.to_string());
+            }
+        }
+
+        let response = app(db, &Config::default())
+            .oneshot(Request::get("/admin/export.zip").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+        assert_eq!(response.headers()[header::CONTENT_DISPOSITION], "attachment; filename=\"orders.zip\"");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+
+        let mut names: Vec<String> = archive.file_names().map(|name| name.unwrap().into_owned()).collect();
+        names.sort();
+        assert_eq!(names, ["Alice.json", "___Bob_-2.json", "___Bob_.json"]);
+
+        let alice: Vec<serde_json::Value> = serde_json::from_reader(archive.by_name("Alice.json").unwrap()).unwrap();
+        let ids: Vec<&str> = alice.iter().map(|order| order["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, alice_ids);
+        assert_eq!(alice[1]["items"], json!(["Lamp"]));
+    }
+
+    #[test]
+    fn test_customer_filename() {
+        let mut taken = std::collections::HashSet::new();
+        assert_eq!(customer_filename("Zoë & Co.", &mut taken), "Zo____Co_.json");
+        assert_eq!(customer_filename("???", &mut taken), "customer.json");
+        assert_eq!(customer_filename("", &mut taken), "customer-2.json");
+        assert_eq!(customer_filename(&"x".repeat(100), &mut taken).len(), 64 + ".json".len());
+    }
+
+    #[tokio::test]
+    async fn test_list_as_csv() {
+        let db = create_test_db();
+        let mut pending = Vec::new();
+        for (customer, status) in [("Alice", "pending"), ("Bob", "shipped"), ("Carol", "pending"), ("Dave", "pending")] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            let id = db::create_order(db.clone(), dto).await.unwrap().id;
+            if status == "pending" {
+                pending.push((id, customer));
+            } else {
+                let update = UpdateStatusDto { status: status.to_string(), expected: None };
+                db::update_status(db.clone(), id, update).await.unwrap();
+            }
+        }
+
+        let request = Request::get("/orders?status=pending&sort=customer&limit=2&offset=1")
+            .header(header::ACCEPT, "text/csv")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(db, &Config::default()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3, "{csv}");
+        assert_eq!(lines[0], "id,customer,items,status,assignee,created_at");
+        for (line, (id, customer)) in lines[1..].iter().zip(&pending[1..]) {
+            assert!(line.starts_with(&format!("{id},{customer},Book,pending,,")), "{line}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_csv() {
+        let db = create_test_db();
+        let csv = "customer,items\n\"Doe, Jane\",\"Book|Pen\"\n,Lamp\nBob,Pen,extra\nCarol,Mug\n";
+        let request = Request::post("/orders/import.csv")
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body(Body::from(csv))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let summary: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(summary["imported"].as_array().unwrap().len(), 2);
+        assert_eq!(summary["errors"].as_array().unwrap().len(), 2);
+        assert_eq!(summary["errors"][0]["line"], 3);
+        assert!(summary["errors"][0]["error"].as_str().unwrap().contains("customer"));
+        // A row the CSV reader can't parse still names its line
+        assert_eq!(summary["errors"][1]["line"], 4);
+        assert!(summary["errors"][1]["error"].as_str().unwrap().contains("fields"), "{summary}");
+
+        let id: Uuid = summary["imported"][0].as_str().unwrap().parse().unwrap();
+        let order = db::get_order(db.clone(), id).await.unwrap();
+        assert_eq!(order.customer, "Doe, Jane");
+        assert_eq!(order.items, ["Book", "Pen"]);
+
+        let request = Request::post("/orders/import.csv")
+            .body(Body::from("name,things\nJane,Book\n"))
+            .unwrap();
+        assert_eq!(send(db, request).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_require_https() {
+        let config = Config { require_https: true, ..Default::default() };
+        let app = app(create_test_db(), &config);
+        let get = |uri: &str, proto: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(proto) = proto {
+                request = request.header("x-forwarded-proto", proto);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        for proto in [Some("http"), None, Some("http, https")] {
+            let response = app.clone().oneshot(get("/orders", proto)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{proto:?}");
+        }
+        let response = app.clone().oneshot(get("/orders", Some("HTTPS"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(get("/", Some("http"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_writes_disabled() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let config = Config {
+            enable_writes: false,
+            ..Default::default()
+        };
+        let app = app(db.clone(), &config);
+
+        let request = Request::post("/orders")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"customer":"Bob","items":["Pen"]}"#))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = Request::delete(format!("/orders/{}", order.id)).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let request = Request::get(format!("/orders/{}", order.id)).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let request = Request::post("/nowhere").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(db.read().await.orders.len(), 1);
+    }
+
+    async fn post_empty(db: Db, uri: String) -> StatusCode {
+        send(db, Request::post(uri).body(Body::empty()).unwrap()).await.0
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_pending_shipped_delivered() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        // Skipping straight to delivered is rejected
+        let status = post_empty(db.clone(), format!("/orders/{}/delivered", order.id)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let request = Request::put(format!("/orders/{}/status", order.id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"status":"shipped"}"#))
+            .unwrap();
+        assert_eq!(send(db.clone(), request).await.0, StatusCode::OK);
+
+        let request = Request::post(format!("/orders/{}/delivered", order.id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"by":"courier-7"}"#))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "delivered");
+
+        // Delivering twice is out of order too
+        let status = post_empty(db, format!("/orders/{}/delivered", order.id)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_not_found_distinguishes_route_from_order() {
+        let db = create_test_db();
+        let request = Request::get("/no/such/path").body(Body::empty()).unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "route_not_found");
+        assert_eq!(json["message"], "Route not found");
+
+        let request = Request::get(format!("/orders/{}", Uuid::new_v4())).body(Body::empty()).unwrap();
+        let (status, body) = send(db, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "order_not_found");
+        assert_eq!(json["message"], "Order not found");
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let request = Request::patch(format!("/orders/{}", order.id))
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .body(Body::from(r#"{"items":["Book","Pen"],"assignee":"bob"}"#))
+            .unwrap();
+        let (status, body) = send(db, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["items"], serde_json::json!(["Book", "Pen"]));
+        assert_eq!(json["assignee"], "bob");
+        assert_eq!(json["customer"], "Alice");
+    }
+
+    fn preflight(origin: &str) -> Request<Body> {
+        Request::options("/orders")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_with_credentials_and_max_age() {
+        let config = Config {
+            cors_origins: vec!["https://app.example.com".to_string()],
+            cors_allow_credentials: true,
+            cors_max_age: Some(Duration::from_secs(600)),
+            ..Config::default()
+        };
+        let response = app(create_test_db(), &config).oneshot(preflight("https://app.example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "POST");
+
+        // Origins outside the list get no grant
+        let response = app(create_test_db(), &config).oneshot(preflight("https://evil.example")).await.unwrap();
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard_is_never_reflected_with_credentials() {
+        let mut config = Config {
+            cors_origins: vec!["*".to_string(), "https://app.example.com".to_string()],
+            ..Config::default()
+        };
+        let response = app(create_test_db(), &config).oneshot(preflight("https://evil.example")).await.unwrap();
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+        // Refused at startup, and even then only the listed origin is granted
+        config.cors_allow_credentials = true;
+        assert!(config.check().is_err());
+        let response = app(create_test_db(), &config).oneshot(preflight("https://evil.example")).await.unwrap();
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        let response = app(create_test_db(), &config).oneshot(preflight("https://app.example.com")).await.unwrap();
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        // Off by default
+        let response = app(create_test_db(), &Config::default()).oneshot(preflight("https://app.example.com")).await.unwrap();
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_freeze_endpoints() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let post = |action: &str| Request::post(format!("/orders/{}/{action}", order.id)).body(Body::empty()).unwrap();
+        let ship = || {
+            Request::put(format!("/orders/{}/status", order.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"status":"shipped"}"#))
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let (status, body) = send(db.clone(), post("freeze")).await;
+            assert_eq!(status, StatusCode::OK);
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["frozen"], true);
+        }
+        let (status, body) = send(db.clone(), ship()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["message"], "Conflict: order is frozen");
+
+        let (status, _) = send(db.clone(), post("unfreeze")).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(db, ship()).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch_with_update_mask() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        let patch = |uri: String| {
+            Request::patch(uri)
+                .header(header::CONTENT_TYPE, "application/merge-patch+json")
+                .body(Body::from(r#"{"status":"shipped","customer":"Mallory"}"#))
+                .unwrap()
+        };
+
+        let (status, body) = send(db.clone(), patch(format!("/orders/{}?update_mask=status", order.id))).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "shipped");
+        assert_eq!(json["customer"], "Alice");
+
+        let (status, _) = send(db, patch(format!("/orders/{}?update_mask=status,price", order.id))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_endpoint() {
+        let db = create_test_db();
+        for customer in ["Alice", "Bob"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+
+        let request = Request::post("/orders/bulk-delete")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        assert_eq!(send(db.clone(), request).await.0, StatusCode::BAD_REQUEST);
+
+        let request = Request::post("/orders/bulk-delete")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"status":"pending"}"#))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["deleted"], 2);
+        assert!(db.read().await.orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admin_stats_counts_orders() {
+        let db = create_test_db();
+        for customer in ["Alice", "Bob"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+
+        let (status, body) = send(db, Request::get("/admin/stats").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["orders"], 2);
+        assert_eq!(json["item_names"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_order_ages_and_metrics() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+        db.write().await.orders.get_mut(&order.id).unwrap().updated_at = Utc::now() - chrono::Duration::seconds(120);
+
+        let (status, body) = send(db.clone(), Request::get("/admin/order-ages").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["pending"]["count"], 1);
+        assert!(json["pending"]["avg_secs"].as_f64().unwrap() >= 120.0);
+        assert!(json["shipped"]["avg_secs"].is_null());
+
+        let (status, body) = send(db, Request::get("/metrics").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("# TYPE order_age_seconds_avg gauge"));
+        assert!(text.contains("orders_total{status=\"shipped\"} 0"));
+        assert!(text.contains("order_age_seconds_max{status=\"pending\"} 12"));
+        assert!(!text.contains("order_age_seconds_avg{status=\"shipped\"}"));
+        // The earlier request was counted even though nothing was logged for it
+        assert!(!text.contains("http_requests_total 0\n"));
+        // Dedup is off in the test store
+        assert!(text.contains("dedup_cache_entries 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_ndjson() {
+        let db = create_test_db();
+        let body = concat!(
+            "{\"customer\":\"Alice\",\"items\":[\"Book\"]}\n",
+            "\n",
+            "{\"customer\":\"\",\"items\":[\"Pen\"]}\n",
+            "not json\n",
+            "{\"customer\":\"Bob\",\"items\":[\"Lamp\"]}",
+        );
+        // Arrives in small chunks that split lines, as a real upload would
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            body.as_bytes().chunks(7).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        let request = Request::post("/orders/bulk")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from_stream(stream::iter(chunks)))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let results: Vec<serde_json::Value> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        let summary: Vec<(u64, u64)> = results
+            .iter()
+            .map(|r| (r["line"].as_u64().unwrap(), r["status"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(summary, [(1, 200), (3, 422), (4, 400), (5, 200)]);
+        assert_eq!(results[0]["order"]["customer"], "Alice");
+        assert_eq!(results[1]["error"]["code"], "validation_failed");
+        assert_eq!(db.read().await.orders.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_line_errors_follow_language_and_format() {
+        let request = Request::post("/orders/bulk")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .header(header::ACCEPT_LANGUAGE, "es")
+            .body(Body::from("{\"customer\":\"\",\"items\":[\"Pen\"]}\n"))
+            .unwrap();
+        let flat = Config { error_format: errors::ErrorFormat::Flat, ..Default::default() };
+        let response = app(create_test_db(), &flat).oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["status"], 422);
+        assert_eq!(
+            result["error"]["details"],
+            json!([{ "field": "customer", "message": "el nombre del cliente no puede estar vacío" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_caps_line_length() {
+        let request = Request::post("/orders/bulk")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from(vec![b'x'; MAX_NDJSON_LINE + 10]))
+            .unwrap();
+        let (_, body) = send(create_test_db(), request).await;
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["status"], 400);
+        assert!(result["error"]["message"].as_str().unwrap().contains("longer than"));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_requires_ndjson() {
+        let request = Request::post("/orders/bulk")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("[]"))
+            .unwrap();
+        assert_eq!(send(create_test_db(), request).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_tag_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let request = Request::post("/orders/bulk-tag")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"filter":{"status":"pending"},"add":["promo"]}"#))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "modified": 1 }));
+        assert_eq!(db::get_order(db.clone(), order.id).await.unwrap().tags.len(), 1);
+
+        let request = Request::post("/orders/bulk-tag")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"filter":{},"add":["promo"]}"#))
+            .unwrap();
+        assert_eq!(send(db, request).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_status_preflight_endpoint() {
+        let db = create_test_db();
+        let dto = CreateOrderDto {
+            customer: "Alice".to_string(),
+            items: vec!["Book".to_string()],
+        };
+        let order = db::create_order(db.clone(), dto).await.unwrap();
+
+        let body = json!({ "updates": [
+            { "id": order.id, "status": "delivered" },
+            { "id": order.id, "status": "shipped" },
+        ] });
+        let request = Request::post("/orders/bulk-status/validate")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, body) = send(db.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let results = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"].clone();
+        assert_eq!(results[0]["legal"], false);
+        assert_eq!(results[0]["error"]["code"], "conflict");
+        assert_eq!(results[1], json!({ "id": order.id, "status": "shipped", "legal": true }));
+        assert_eq!(db::get_order(db.clone(), order.id).await.unwrap().status, "pending");
+
+        // A stale `expected` fails as the real change would, and `?idempotent=true` is honored too
+        let validate = |query: &str, body: serde_json::Value| {
+            Request::post(format!("/orders/bulk-status/validate{query}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let stale = json!({ "updates": [{ "id": order.id, "status": "shipped", "expected": "shipped" }] });
+        let (_, body) = send(db.clone(), validate("", stale)).await;
+        let results = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"].clone();
+        assert_eq!(results[0]["error"]["code"], "status_mismatch");
+
+        let repeat = json!({ "updates": [{ "id": order.id, "status": "pending" }] });
+        let (_, body) = send(db.clone(), validate("", repeat.clone())).await;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"][0]["legal"], false);
+        let (_, body) = send(db, validate("?idempotent=true", repeat)).await;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"][0]["legal"], true);
+    }
+
+    /// Backup with a valid order on lines 1 and 3 and one with an empty customer on line 2
+    async fn backup_with_invalid_line() -> Bytes {
+        let db = create_test_db();
+        for customer in ["Alice", "Bob", "Carol"] {
+            let dto = CreateOrderDto {
+                customer: customer.to_string(),
+                items: vec!["Book".to_string()],
+            };
+            db::create_order(db.clone(), dto).await.unwrap();
+        }
+        let mut orders = db::snapshot_orders(db, CreatedRange::default()).await;
+        orders[1].customer = String::new();
+        let lines: Vec<String> = orders.iter().map(|order| serde_json::to_string(order).unwrap()).collect();
+        Bytes::from(lines.join("\n"))
+    }
+
+    fn restore_request(query: &str, backup: Bytes) -> Request<Body> {
+        Request::post(format!("/admin/restore{query}"))
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from(backup))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_atomic_restore_restores_nothing_on_invalid_line() {
+        let backup = backup_with_invalid_line().await;
+        for query in ["", "?mode=atomic"] {
+            let db = create_test_db();
+            let (status, _) = send(db.clone(), restore_request(query, backup.clone())).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(db.read().await.orders.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_atomic_restore_refuses_payloads_over_the_cap() {
+        let line = |_| json!({ "id": Uuid::new_v4(), "customer": "Alice", "items": ["Book"], "status": "pending" }).to_string();
+        let backup = (0..=MAX_ATOMIC_RESTORE).map(line).collect::<Vec<_>>().join("\n");
+        let db = create_test_db();
+        let (status, body) = send(db.clone(), restore_request("", backup.clone().into())).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"], "payload_too_large");
+        assert!(db.read().await.orders.is_empty());
+
+        let (status, _) = send(db.clone(), restore_request("?mode=best_effort", backup.into())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(db.read().await.orders.len(), MAX_ATOMIC_RESTORE + 1);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_restore_reports_skipped_lines() {
+        let db = create_test_db();
+        let (status, body) = send(db.clone(), restore_request("?mode=best_effort", backup_with_invalid_line().await)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["restored"], 2);
+        assert_eq!(json["skipped"].as_array().unwrap().len(), 1);
+        assert_eq!(json["skipped"][0]["line"], 2);
+        assert_eq!(json["skipped"][0]["status"], 422);
+        assert!(json["skipped"][0]["error"]["details"]["customer"].is_array());
+
+        let mut customers: Vec<String> = db.read().await.orders.values().map(|o| o.customer.clone()).collect();
+        customers.sort();
+        assert_eq!(customers, ["Alice", "Carol"]);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_restore_inserts_before_the_body_ends() {
+        let db = create_test_db();
+        let (lines, rx) = tokio::sync::mpsc::channel::<Bytes>(RESTORE_BATCH + 1);
+        let body = Body::from_stream(stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| (Ok::<_, std::io::Error>(chunk), rx))
+        }));
+        let request = Request::post("/admin/restore?mode=best_effort")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .unwrap();
+        let restore = tokio::spawn(send(db.clone(), request));
+
+        let line = |customer: &str| {
+            let order = Order::new(customer.to_string(), vec!["Book".to_string()], chrono::Utc::now());
+            Bytes::from(format!("{}\n", serde_json::to_string(&order).unwrap()))
+        };
+        for n in 0..=RESTORE_BATCH {
+            lines.send(line(&format!("customer {n}"))).await.unwrap();
+        }
+        // A full batch is in while the body is still open
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while db.read().await.orders.len() < RESTORE_BATCH {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(!restore.is_finished());
+
+        drop(lines);
+        let (status, body) = restore.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["restored"], RESTORE_BATCH + 1);
+        assert_eq!(db.read().await.orders.len(), RESTORE_BATCH + 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_malformed_line() {
+        let db = create_test_db();
+        let request = Request::post("/admin/restore")
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from("not json\n"))
+            .unwrap();
+
+        let (status, body) = send(db, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["message"].as_str().unwrap().contains("line 1"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use tantivy::{
+    collector::TopDocs,
+    doc,
+    query::QueryParser,
+    schema::{Field, Schema, Value, STORED, STRING, TEXT},
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+use uuid::Uuid;
+
+use crate::models::Order;
+
+/// Smallest writer arena tantivy accepts
+const WRITER_MEMORY: usize = 15_000_000;
+
+/// In-RAM full-text index over customer names and items, keyed by order id
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: IndexWriter,
+    id: Field,
+    text: Field,
+}
+
+impl SearchIndex {
+    pub fn new() -> tantivy::Result<Self> {
+        let mut schema = Schema::builder();
+        let id = schema.add_text_field("id", STRING | STORED);
+        let text = schema.add_text_field("text", TEXT);
+        let index = Index::create_in_ram(schema.build());
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+        // Reloaded by hand after each commit so a write is searchable straight away
+        let reader = index.reader_builder().reload_policy(ReloadPolicy::Manual).try_into()?;
+        Ok(Self { index, reader, writer, id, text })
+    }
+
+    /// Indexes the order, replacing any earlier version of it
+    pub fn upsert(&mut self, order: &Order) -> tantivy::Result<()> {
+        self.writer.delete_term(Term::from_field_text(self.id, &order.id.to_string()));
+        let text = std::iter::once(order.customer.as_str())
+            .chain(order.items.iter().map(|item| item.as_ref()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.writer.add_document(doc!(self.id => order.id.to_string(), self.text => text))?;
+        self.commit()
+    }
+
+    pub fn remove(&mut self, ids: &[Uuid]) -> tantivy::Result<()> {
+        for id in ids {
+            self.writer.delete_term(Term::from_field_text(self.id, &id.to_string()));
+        }
+        self.commit()
+    }
+
+    /// Ids of the best matching orders, most relevant first
+    pub fn search(&self, query: &str, limit: usize) -> tantivy::Result<Vec<Uuid>> {
+        let searcher = self.reader.searcher();
+        // Lenient parsing treats stray query syntax as plain terms instead of failing
+        let (query, _) = QueryParser::for_index(&self.index, vec![self.text]).parse_query_lenient(query);
+        let hits = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let mut ids = Vec::with_capacity(hits.len());
+        for (_score, address) in hits {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            if let Some(id) = doc.get_first(self.id).and_then(|v| v.as_str()).and_then(|v| v.parse().ok()) {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn commit(&mut self) -> tantivy::Result<()> {
+        self.writer.commit()?;
+        self.reader.reload()
+    }
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self::new().expect("in-RAM search index")
+    }
+}
+
+impl fmt::Debug for SearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchIndex").field("docs", &self.reader.searcher().num_docs()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(customer: &str, items: &[&str]) -> Order {
+        Order::new(customer.to_string(), items.iter().map(|i| i.to_string()).collect(), chrono::Utc::now())
+    }
+
+    #[test]
+    fn test_multi_term_query_is_ranked() {
+        let mut index = SearchIndex::new().unwrap();
+        let both = order("Jane Doe", &["red widget"]);
+        let one = order("John Smith", &["widget"]);
+        let none = order("Carol", &["lamp"]);
+        for o in [&both, &one, &none] {
+            index.upsert(o).unwrap();
+        }
+
+        assert_eq!(index.search("jane widget", 10).unwrap(), [both.id, one.id]);
+    }
+
+    #[test]
+    fn test_upsert_and_remove() {
+        let mut index = SearchIndex::new().unwrap();
+        let mut o = order("Jane", &["widget"]);
+        index.upsert(&o).unwrap();
+
+        o.items = vec!["lamp".into()];
+        index.upsert(&o).unwrap();
+        assert!(index.search("widget", 10).unwrap().is_empty());
+        assert_eq!(index.search("lamp", 10).unwrap(), [o.id]);
+
+        index.remove(&[o.id]).unwrap();
+        assert!(index.search("lamp", 10).unwrap().is_empty());
+    }
+}
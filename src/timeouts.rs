@@ -0,0 +1,142 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{errors::ApiError, logging::parse_route_millis};
+
+/// How long a request may take to produce its response: one global limit, overridden per matched
+/// path (e.g. `/orders/export.csv`) for routes that legitimately need longer
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteTimeouts {
+    /// Limit for routes without an override (none when unset)
+    pub default: Option<Duration>,
+    routes: HashMap<String, Duration>,
+}
+
+/// Routes that read a streamed upload before they answer. They are exempt unless overridden, as a
+/// cut-off upload may already be partly stored and a 503 would invite resending all of it.
+const STREAMED_UPLOADS: [&str; 2] = ["/admin/restore", "/orders/import.csv"];
+
+impl RouteTimeouts {
+    /// A global limit plus `route=millis` overrides; an override of 0 exempts the route
+    pub fn new(default: Option<Duration>, overrides: &str) -> Self {
+        let mut routes: HashMap<String, Duration> = STREAMED_UPLOADS.iter().map(|route| (route.to_string(), Duration::ZERO)).collect();
+        routes.extend(parse_route_millis(overrides));
+        Self { default, routes }
+    }
+
+    /// The route's limit, if it has one
+    pub fn for_route(&self, route: &str) -> Option<Duration> {
+        match self.routes.get(route) {
+            Some(limit) => Some(*limit).filter(|limit| !limit.is_zero()),
+            None => self.default,
+        }
+    }
+}
+
+impl Default for RouteTimeouts {
+    fn default() -> Self {
+        Self::new(Some(Duration::from_secs(30)), "")
+    }
+}
+
+/// Answers 503 when the handler has not produced a response within its route's limit.
+/// Only the time to the response head counts, so a streamed body such as `/orders/bulk` output
+/// is never cut off however long it runs. Streamed uploads are exempt by default (see `STREAMED_UPLOADS`).
+pub async fn enforce_timeouts(State(timeouts): State<Arc<RouteTimeouts>>, request: Request, next: Next) -> Response {
+    let limit = request.extensions().get::<MatchedPath>().and_then(|path| timeouts.for_route(path.as_str()));
+    let Some(limit) = limit else {
+        return next.run(request).await;
+    };
+    match tokio::time::timeout(limit, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!(?limit, "Request timed out");
+            ApiError::Unavailable("request timed out".into()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::post, Router};
+    use futures_util::{stream, StreamExt};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_overrides_fall_back_to_default() {
+        let timeouts = RouteTimeouts::new(Some(Duration::from_secs(5)), "/orders/export.csv=60000,/events=0,/x=soon");
+        assert_eq!(timeouts.for_route("/orders/export.csv"), Some(Duration::from_secs(60)));
+        assert_eq!(timeouts.for_route("/events"), None);
+        assert_eq!(timeouts.for_route("/x"), Some(Duration::from_secs(5)));
+        assert_eq!(timeouts.for_route("/orders"), Some(Duration::from_secs(5)));
+        assert_eq!(RouteTimeouts::new(None, "").for_route("/orders"), None);
+        assert_eq!(timeouts.for_route("/admin/restore"), None);
+        assert_eq!(timeouts.for_route("/orders/import.csv"), None);
+        let timeouts = RouteTimeouts::new(Some(Duration::from_secs(5)), "/admin/restore=60000");
+        assert_eq!(timeouts.for_route("/admin/restore"), Some(Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn test_same_delay_passes_long_budget_and_times_out_short_one() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        let chunks = || async {
+            let chunks = stream::iter(0..3).then(|n| async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok::<_, std::io::Error>(n.to_string())
+            });
+            Body::from_stream(chunks)
+        };
+        let timeouts = Arc::new(RouteTimeouts::new(Some(Duration::from_millis(10)), "/orders/export.csv=1000"));
+        let app = Router::new()
+            .route("/orders/export.csv", post(slow))
+            .route("/orders", post(slow))
+            .route("/orders/bulk", post(chunks))
+            .layer(middleware::from_fn_with_state(timeouts, enforce_timeouts));
+        let send = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(axum::http::Request::post(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let status = response.status();
+                (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+            }
+        };
+
+        assert_eq!(send("/orders/export.csv").await.0, StatusCode::OK);
+        assert_eq!(send("/orders").await.0, StatusCode::SERVICE_UNAVAILABLE);
+        // Streaming for longer than the limit is fine once the response has started
+        assert_eq!(send("/orders/bulk").await, (StatusCode::OK, "012".into()));
+    }
+
+    #[tokio::test]
+    async fn test_slow_streamed_upload_outlives_the_limit() {
+        let upload = |body: Body| async move { axum::body::to_bytes(body, usize::MAX).await.unwrap() };
+        let timeouts = Arc::new(RouteTimeouts::new(Some(Duration::from_millis(10)), ""));
+        let app = Router::new()
+            .route("/admin/restore", post(upload))
+            .route("/orders", post(upload))
+            .layer(middleware::from_fn_with_state(timeouts, enforce_timeouts));
+        let send = |uri: &'static str| {
+            let chunks = stream::iter(0..3).then(|n| async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok::<_, std::io::Error>(n.to_string())
+            });
+            let request = axum::http::Request::post(uri).body(Body::from_stream(chunks)).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+            }
+        };
+
+        assert_eq!(send("/admin/restore").await, (StatusCode::OK, "012".into()));
+        assert_eq!(send("/orders").await.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}